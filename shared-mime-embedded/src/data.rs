@@ -1 +1 @@
-pub(crate) static EMBED_BYTES: &[u8] = include_bytes!(env!("EMBEDDED_MIME_PATH"));
+include!(concat!(env!("OUT_DIR"), "/mimedata.rs"));
@@ -1,22 +1,26 @@
-use data::EMBED_BYTES;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::sync::Arc;
+
+use data::EMBEDDED_INDEX;
 use log::*;
-use postcard::from_bytes;
 
-use shared_mime::record::MimeTypeRecord;
 #[cfg(feature = "xdg-runtime")]
-use shared_mime::runtime::load_xdg_mime_info;
-use shared_mime::LoadError;
-pub use shared_mime::{Answer, FileQuery, FileQueryBuilder, MimeDB};
+use shared_mime::runtime::{load_xdg_mime_info_with, LoadMode, MimeDbLoader};
+pub use shared_mime::{Answer, FileQuery, FileQueryBuilder, MimeDB, OwnedAnswer};
+use shared_mime::{GlobalDb, LoadError, QueryError};
 
 mod data;
 #[cfg(test)]
 mod tests;
 
+static GLOBAL_DB: GlobalDb = GlobalDb::new(load_global);
+
 /// Get the embedded MIME info database.
+///
+/// The database is precompiled, so this does not need to parse or copy it.
 pub fn embedded_mime_db() -> MimeDB {
-    let mut db = MimeDB::new();
-    let recs: Vec<MimeTypeRecord> = from_bytes(EMBED_BYTES).expect("embedded data decode failed");
-    db.add_records(recs);
+    let db = MimeDB::from_static(&EMBEDDED_INDEX);
     debug!(
         "loaded embedded MIME info with {} types and {} globs",
         db.type_count(),
@@ -25,12 +29,30 @@ pub fn embedded_mime_db() -> MimeDB {
     db
 }
 
+/// Extension to add the embedded database as a layer of a [MimeDbLoader].
+#[cfg(feature = "xdg-runtime")]
+pub trait EmbeddedLayer {
+    /// Add a layer for the embedded database.
+    ///
+    /// As the first layer, this uses the precompiled database directly;
+    /// elsewhere, its rules are copied into the database being loaded.
+    fn embedded(self) -> Self;
+}
+
+#[cfg(feature = "xdg-runtime")]
+impl EmbeddedLayer for MimeDbLoader {
+    fn embedded(self) -> Self {
+        self.precompiled(&EMBEDDED_INDEX)
+    }
+}
+
 /// Load the MIME info database.
 ///
 /// This starts by loading the embedded database. If the `xdg-runtime` feature
 /// is enabled, it then loads the XDG shared mime database installed on the
 /// system, treating the embedded database as a directory of mime information
-/// that preceeds any system information.
+/// that preceeds any system information.  System package files that cannot be
+/// parsed are skipped with a warning.
 pub fn load_mime_db() -> Result<MimeDB, LoadError> {
     debug!("loading embedded MIME database");
     let mut db = embedded_mime_db();
@@ -40,7 +62,7 @@ pub fn load_mime_db() -> Result<MimeDB, LoadError> {
         debug!("loading runtime MIME database");
         let nt = db.type_count();
         let ng = db.glob_count();
-        match load_xdg_mime_info() {
+        match load_xdg_mime_info_with(LoadMode::Lenient) {
             Ok(info) => db.add_shared_mime_info(info),
             Err(e) => warn!("error loading MIME info: {:?}", e),
         }
@@ -53,3 +75,40 @@ pub fn load_mime_db() -> Result<MimeDB, LoadError> {
 
     Ok(db)
 }
+
+/// Get the global MIME database, loading it with [load_mime_db] if necessary.
+///
+/// If the database cannot be loaded, the embedded database is used.
+pub fn global() -> Arc<MimeDB> {
+    GLOBAL_DB.get()
+}
+
+/// Replace the global MIME database, returning the previous one.
+pub fn set_global(db: MimeDB) -> Option<Arc<MimeDB>> {
+    GLOBAL_DB.set(db)
+}
+
+/// Guess the type of a file using the global database.
+pub fn guess_for_path<P: AsRef<Path>>(path: P) -> Result<OwnedAnswer, QueryError> {
+    GLOBAL_DB.guess_for_path(path)
+}
+
+/// Guess the type of a file from its name using the global database.
+pub fn guess_for_name<S: AsRef<OsStr>>(name: S) -> OwnedAnswer {
+    GLOBAL_DB.guess_for_name(name)
+}
+
+/// Guess the type of a file from its content using the global database.
+pub fn guess_for_bytes(data: &[u8]) -> OwnedAnswer {
+    GLOBAL_DB.guess_for_bytes(data)
+}
+
+fn load_global() -> MimeDB {
+    match load_mime_db() {
+        Ok(db) => db,
+        Err(e) => {
+            warn!("error loading MIME database: {}", e);
+            embedded_mime_db()
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::embedded_mime_db;
+
+#[test]
+fn test_default_description() {
+    let db = embedded_mime_db();
+    assert_eq!(db.description("image/png"), Some("PNG image"));
+}
+
+#[test]
+fn test_locale_description() {
+    let db = embedded_mime_db();
+    assert_eq!(
+        db.description_for_locale("image/png", &["de_DE.UTF-8"]),
+        Some("PNG-Bild")
+    );
+    assert_eq!(
+        db.description_for_locale("image/png", &["pt_BR"]),
+        Some("Imagem PNG")
+    );
+    assert_eq!(
+        db.description_for_locale("image/png", &["pt_PT"]),
+        Some("imagem PNG")
+    );
+}
+
+#[test]
+fn test_locale_description_fallback() {
+    let db = embedded_mime_db();
+    assert_eq!(
+        db.description_for_locale("image/png", &["x-hackem", "de"]),
+        Some("PNG-Bild")
+    );
+    assert_eq!(
+        db.description_for_locale("image/png", &["C"]),
+        Some("PNG image")
+    );
+}
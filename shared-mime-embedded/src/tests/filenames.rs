@@ -1,5 +1,7 @@
 use std::ffi::OsString;
 
+use shared_mime::MatchSource;
+
 use crate::embedded_mime_db;
 
 #[test]
@@ -24,3 +26,60 @@ fn test_json_file() {
     assert_eq!(answer.best(), Some("application/json"));
     assert!(answer.all_types().len() > 1);
 }
+
+#[test]
+fn test_multiple_extensions() {
+    // the longest pattern wins, without needing to collapse supertypes
+    let mut db = embedded_mime_db();
+    db.set_collapse_supertypes(false);
+    let answer = db.query_filename(OsString::from("foo.tar.gz"));
+    assert_eq!(answer.best(), Some("application/x-compressed-tar"));
+    assert_eq!(answer.all_types().len(), 1);
+}
+
+#[test]
+fn test_literal_file() {
+    let db = embedded_mime_db();
+    let answer = db.query_filename(OsString::from("Makefile"));
+    assert_eq!(answer.best(), Some("text/x-makefile"));
+}
+
+#[test]
+fn test_json_no_collapse() {
+    let mut db = embedded_mime_db();
+    db.set_collapse_supertypes(false);
+    let answer = db.query_filename(OsString::from("foo.json"));
+    assert!(answer.is_ambiguous());
+}
+
+#[test]
+fn test_type_extensions() {
+    let db = embedded_mime_db();
+    assert_eq!(db.primary_extension("image/png"), Some("png"));
+    assert!(db
+        .extensions_for_type("image/jpeg", false)
+        .contains(&"jpeg"));
+    assert!(db
+        .globs_for_type("application/x-compressed-tar", false)
+        .contains(&"*.tar.gz"));
+    assert!(db
+        .extensions_for_type("application/x-compressed-tar", true)
+        .contains(&"gz"));
+}
+
+#[test]
+fn test_owned_answer() {
+    let db = embedded_mime_db();
+    let answer = db.query_filename("foo.png").into_owned();
+    assert_eq!(answer.best(), Some("image/png"));
+    let cand = &answer.candidates[0];
+    assert_eq!(
+        cand.source,
+        MatchSource::Glob {
+            pattern: "*.png".into(),
+            weight: 50
+        }
+    );
+    let answer = std::thread::spawn(move || answer).join().unwrap();
+    assert!(answer.is_definite());
+}
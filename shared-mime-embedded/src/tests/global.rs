@@ -0,0 +1,13 @@
+use crate::{global, guess_for_bytes, guess_for_name};
+
+#[test]
+fn test_global_db() {
+    let db = global();
+    assert!(db.type_count() > 0);
+    assert!(std::sync::Arc::ptr_eq(&db, &global()));
+    assert_eq!(guess_for_name("foo.png").best(), Some("image/png"));
+    assert_eq!(
+        guess_for_bytes(b"%PDF-1.7\n").best(),
+        Some("application/pdf")
+    );
+}
@@ -0,0 +1,36 @@
+use crate::embedded_mime_db;
+
+#[test]
+fn test_default_icon() {
+    let db = embedded_mime_db();
+    assert_eq!(db.icon_name("image/png"), "image-png");
+    assert_eq!(db.generic_icon_name("image/png"), "image-x-generic");
+}
+
+#[test]
+fn test_generic_icon() {
+    let db = embedded_mime_db();
+    assert_eq!(
+        db.generic_icon_name("application/vnd.oasis.opendocument.text"),
+        "x-office-document"
+    );
+}
+
+#[test]
+fn test_icon_names() {
+    let db = embedded_mime_db();
+    let names = db.icon_names("image/png");
+    assert_eq!(names[0], "image-png");
+    assert_eq!(names[1], "image-x-generic");
+}
+
+#[test]
+fn test_acronym() {
+    let db = embedded_mime_db();
+    assert_eq!(db.acronym("application/mathml+xml"), Some("MathML"));
+    assert_eq!(
+        db.expanded_acronym("application/mathml+xml"),
+        Some("Mathematical Markup Language")
+    );
+    assert_eq!(db.acronym("text/x-hackem-muche"), None);
+}
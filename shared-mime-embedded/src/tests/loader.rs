@@ -0,0 +1,83 @@
+//! Layer the embedded database with other sources.
+use shared_mime::record::{GlobRule, MimeTypeRecord};
+use shared_mime::runtime::MimeDbLoader;
+use shared_mime::MimeDB;
+
+use crate::{embedded_mime_db, EmbeddedLayer};
+
+fn png_override() -> Vec<MimeTypeRecord> {
+    vec![MimeTypeRecord {
+        name: "image/x-hackem".into(),
+        globs: vec![GlobRule {
+            pattern: "*.png".into(),
+            weight: 50,
+            case_sensitive: false,
+        }],
+        ..Default::default()
+    }]
+}
+
+fn png_type(db: &MimeDB) -> Option<String> {
+    db.query_filename("foo.png").best().map(|s| s.to_string())
+}
+
+#[test]
+fn test_embedded_first() {
+    let db = MimeDbLoader::new()
+        .embedded()
+        .records("override", png_override())
+        .load()
+        .expect("load failed");
+    assert_eq!(db.type_count(), embedded_mime_db().type_count() + 1);
+    assert_eq!(png_type(&db).as_deref(), Some("image/x-hackem"));
+}
+
+#[test]
+fn test_embedded_last() {
+    let db = MimeDbLoader::new()
+        .records("override", png_override())
+        .embedded()
+        .load()
+        .expect("load failed");
+    assert_eq!(db.type_count(), embedded_mime_db().type_count() + 1);
+    assert_eq!(png_type(&db).as_deref(), Some("image/png"));
+    let ans = db.query_filename("foo.png").into_owned();
+    assert_eq!(
+        ans.candidates[0].origin.as_deref(),
+        Some("freedesktop.org.xml")
+    );
+}
+
+#[test]
+fn test_embedded_layer_queries() {
+    let db = MimeDbLoader::new()
+        .records("empty", vec![])
+        .embedded()
+        .load()
+        .expect("load failed");
+    let embedded = embedded_mime_db();
+    assert_eq!(db.glob_count(), embedded.glob_count());
+    assert_eq!(db.magic_count(), embedded.magic_count());
+    assert_eq!(db.magic_extent(), embedded.magic_extent());
+    for name in ["foo.png", "Makefile", "a.tar.gz", "x.C"] {
+        assert_eq!(
+            db.query_filename(name).all_types(),
+            embedded.query_filename(name).all_types(),
+        );
+    }
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    assert_eq!(db.query_magic(png).best(), Some("image/png"));
+    let ans = db.query_magic(png).into_owned();
+    assert_eq!(
+        ans.candidates[0].origin.as_deref(),
+        Some("freedesktop.org.xml")
+    );
+    assert_eq!(
+        db.description("image/png"),
+        embedded.description("image/png")
+    );
+    assert_eq!(
+        db.parents("image/svg+xml"),
+        embedded.parents("image/svg+xml")
+    );
+}
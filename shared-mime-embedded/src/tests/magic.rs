@@ -0,0 +1,132 @@
+use std::ffi::OsStr;
+
+use crate::{embedded_mime_db, FileQuery};
+use shared_mime::{MatchSource, TextRules};
+
+#[test]
+fn test_png_magic() {
+    let db = embedded_mime_db();
+    let answer = db.query_magic(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+    assert_eq!(answer.best(), Some("image/png"));
+}
+
+#[test]
+fn test_pdf_magic() {
+    let db = embedded_mime_db();
+    let answer = db.query_magic(b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n");
+    assert_eq!(answer.best(), Some("application/pdf"));
+}
+
+#[test]
+fn test_nested_magic() {
+    let db = embedded_mime_db();
+    let answer = db.query_magic(b"RIFF\x24\x08\0\0WAVEfmt ");
+    assert_eq!(answer.best(), Some("audio/x-wav"));
+}
+
+#[test]
+fn test_no_magic() {
+    let db = embedded_mime_db();
+    let answer = db.query_magic(b"\x01\x02\x03\x04hackem muche");
+    assert!(answer.is_unknown());
+}
+
+#[test]
+fn test_magic_extent() {
+    let db = embedded_mime_db();
+    assert!(db.magic_count() > 0);
+    assert!(db.magic_extent() >= 8);
+}
+
+#[test]
+fn test_query_unnamed_content() {
+    let db = embedded_mime_db();
+    let query = FileQuery::builder()
+        .content(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR")
+        .build();
+    let answer = db.query(&query).expect("query failed");
+    assert_eq!(answer.best(), Some("image/png"));
+}
+
+#[test]
+fn test_query_misleading_name() {
+    let db = embedded_mime_db();
+    let query = FileQuery::builder()
+        .filename(OsStr::new("picture.txt"))
+        .content(b"<!DOCTYPE svg PUBLIC \"-//W3C//DTD SVG 1.1//EN\">")
+        .build();
+    let answer = db.query(&query).expect("query failed");
+    assert_eq!(answer.best(), Some("image/svg+xml"));
+}
+
+#[test]
+fn test_query_low_priority_magic() {
+    let db = embedded_mime_db();
+    let query = FileQuery::builder()
+        .filename(OsStr::new("notes.txt"))
+        .content(b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n")
+        .build();
+    let answer = db.query(&query).expect("query failed");
+    assert_eq!(answer.best(), Some("text/plain"));
+}
+
+#[test]
+fn test_query_glob_refines_magic() {
+    let db = embedded_mime_db();
+    let query = FileQuery::builder()
+        .filename(OsStr::new("report.docx"))
+        .content(b"PK\x03\x04\x14\0\x06\0")
+        .build();
+    let answer = db.query(&query).expect("query failed");
+    assert_eq!(
+        answer.best(),
+        Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document")
+    );
+}
+
+#[test]
+fn test_query_text_fallback() {
+    let db = embedded_mime_db();
+    let query = FileQuery::builder()
+        .filename(OsStr::new("LICENSE"))
+        .content(b"Permission is hereby granted, free of charge, to any person\n")
+        .build();
+    let answer = db.query(&query).expect("query failed");
+    assert_eq!(answer.best(), Some("text/plain"));
+}
+
+#[test]
+fn test_query_binary_fallback() {
+    let db = embedded_mime_db();
+    let query = FileQuery::builder()
+        .filename(OsStr::new("blob"))
+        .content(b"\x01\x02\x03\x04hackem muche")
+        .build();
+    let answer = db.query(&query).expect("query failed");
+    assert_eq!(answer.best(), Some("application/octet-stream"));
+}
+
+#[test]
+fn test_query_text_disabled() {
+    let mut db = embedded_mime_db();
+    db.set_text_rules(TextRules::disabled());
+    let query = FileQuery::builder()
+        .filename(OsStr::new("notes"))
+        .content(b"Read me first.\n")
+        .build();
+    let answer = db.query(&query).expect("query failed");
+    assert_eq!(answer.best(), Some("application/octet-stream"));
+}
+
+#[test]
+fn test_magic_provenance() {
+    let db = embedded_mime_db();
+    let answer = db.query_magic(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+    let cands = answer.candidates();
+    assert_eq!(cands.len(), 1);
+    assert!(matches!(
+        &cands[0].source,
+        MatchSource::Magic { offset: 0, value, .. } if value.as_ref() == b"\x89PNG"
+    ));
+    assert_eq!(cands[0].origin.as_deref(), Some("freedesktop.org.xml"));
+}
@@ -1,2 +1,13 @@
+mod descriptions;
 mod filenames;
+mod global;
+mod icons;
+#[cfg(feature = "xdg-runtime")]
+mod loader;
+mod magic;
+#[cfg(feature = "xdg-runtime")]
+mod precompiled;
 mod subclasses;
+mod threads;
+mod trees;
+mod xml;
@@ -0,0 +1,99 @@
+//! Check the precompiled index against a database built from the package.
+use std::path::Path;
+
+use shared_mime::runtime::parse_mime_package;
+use shared_mime::MimeDB;
+
+use crate::embedded_mime_db;
+
+fn built_mime_db() -> (MimeDB, Vec<String>) {
+    let path =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("shared-mime-info/data/freedesktop.org.xml.in");
+    let records = parse_mime_package(&path)
+        .expect("failed to parse package")
+        .into_records();
+    let names = records.iter().map(|r| r.name.clone()).collect();
+    let mut db = MimeDB::new();
+    db.add_records(records);
+    (db, names)
+}
+
+#[test]
+fn test_static_counts() {
+    let (built, _names) = built_mime_db();
+    let db = embedded_mime_db();
+    assert_eq!(db.type_count(), built.type_count());
+    assert_eq!(db.glob_count(), built.glob_count());
+    assert_eq!(db.magic_count(), built.magic_count());
+    assert_eq!(db.treemagic_count(), built.treemagic_count());
+    assert_eq!(db.magic_extent(), built.magic_extent());
+}
+
+#[test]
+fn test_static_type_info() {
+    let (built, names) = built_mime_db();
+    let db = embedded_mime_db();
+    for name in names {
+        assert_eq!(db.description(&name), built.description(&name));
+        assert_eq!(
+            db.description_for_locale(&name, &["pt_BR"]),
+            built.description_for_locale(&name, &["pt_BR"])
+        );
+        assert_eq!(db.acronym(&name), built.acronym(&name));
+        assert_eq!(db.icon_names(&name), built.icon_names(&name));
+        assert_eq!(db.aliases(&name), built.aliases(&name));
+        assert_eq!(db.parents(&name), built.parents(&name));
+    }
+}
+
+#[test]
+fn test_static_queries() {
+    let (built, _names) = built_mime_db();
+    let db = embedded_mime_db();
+    for name in [
+        "foo.png", "FOO.PDF", "README", "Makefile", "a.tar.gz", "x.c", "x.C",
+    ] {
+        assert_eq!(
+            db.query_filename(name).all_types(),
+            built.query_filename(name).all_types(),
+        );
+    }
+    for data in [
+        &b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"[..],
+        b"RIFF\x24\x08\0\0WAVEfmt ",
+        b"\xd4\xc3\xb2\xa1\x02\x00\x04\x00",
+        b"<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\">",
+    ] {
+        assert_eq!(
+            db.query_magic(data).all_types(),
+            built.query_magic(data).all_types(),
+        );
+        assert_eq!(
+            db.query_xml(data).all_types(),
+            built.query_xml(data).all_types(),
+        );
+    }
+}
+
+#[test]
+fn test_static_add_records() {
+    use shared_mime::record::{GlobRule, MimeTypeRecord};
+
+    let mut db = embedded_mime_db();
+    let n = db.glob_count();
+    db.add_records(vec![MimeTypeRecord {
+        name: "application/x-hackem".into(),
+        globs: vec![GlobRule {
+            pattern: "*.hkm".into(),
+            weight: 50,
+            case_sensitive: false,
+        }],
+        ..Default::default()
+    }]);
+    assert_eq!(db.glob_count(), n + 1);
+    assert_eq!(
+        db.query_filename("a.hkm").best(),
+        Some("application/x-hackem")
+    );
+    assert_eq!(db.query_filename("a.png").best(), Some("image/png"));
+}
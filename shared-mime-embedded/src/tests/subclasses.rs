@@ -53,3 +53,18 @@ fn test_inode_not_subtype() {
         assert!(!db.is_subtype(it, "application/octet-stream"));
     }
 }
+
+#[test]
+fn test_alias_subtype() {
+    let db = embedded_mime_db();
+    assert!(db.is_alias("application/x-javascript"));
+    assert_eq!(
+        db.canonical("application/x-javascript"),
+        "application/javascript"
+    );
+    assert!(db.is_subtype("application/x-javascript", "text/plain"));
+    assert_eq!(
+        db.description("application/x-javascript"),
+        db.description("application/javascript")
+    );
+}
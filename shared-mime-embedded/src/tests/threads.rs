@@ -0,0 +1,23 @@
+use std::sync::Arc;
+use std::thread;
+
+use crate::embedded_mime_db;
+
+#[test]
+fn test_shared_db() {
+    let db = Arc::new(embedded_mime_db());
+    let workers: Vec<_> = ["a.png", "b.txt", "c.json", "d.pdf"]
+        .into_iter()
+        .map(|name| {
+            let db = db.clone();
+            thread::spawn(move || {
+                assert!(db.is_subtype("application/json", "text/plain"));
+                db.query_filename(name).best().map(|s| s.to_string())
+            })
+        })
+        .collect();
+    let types: Vec<_> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+    assert_eq!(types[0].as_deref(), Some("image/png"));
+    assert_eq!(types[1].as_deref(), Some("text/plain"));
+    assert_eq!(types[3].as_deref(), Some("application/pdf"));
+}
@@ -0,0 +1,53 @@
+use std::fs;
+
+use tempfile::tempdir;
+
+use crate::embedded_mime_db;
+
+#[test]
+fn test_camera_card() {
+    let db = embedded_mime_db();
+    assert!(db.treemagic_count() > 0);
+    let tmp = tempdir().expect("failed to create scratch dir");
+    let root = tmp.path();
+    fs::create_dir(root.join("DCIM")).unwrap();
+    fs::write(root.join("DCIM").join("IMG_0001.JPG"), b"").unwrap();
+    let answer = db.query_tree(root).expect("query failed");
+    assert!(answer.all_types().contains(&"x-content/image-dcf"));
+}
+
+#[test]
+fn test_empty_camera_card() {
+    let db = embedded_mime_db();
+    let tmp = tempdir().expect("failed to create scratch dir");
+    let root = tmp.path();
+    fs::create_dir(root.join("DCIM")).unwrap();
+    let answer = db.query_tree(root).expect("query failed");
+    assert!(answer.is_unknown());
+}
+
+#[test]
+fn test_video_dvd() {
+    let db = embedded_mime_db();
+    let tmp = tempdir().expect("failed to create scratch dir");
+    let root = tmp.path();
+    fs::create_dir(root.join("VIDEO_TS")).unwrap();
+    fs::write(root.join("VIDEO_TS").join("VIDEO_TS.IFO"), b"DVDVIDEO-VMG").unwrap();
+    let answer = db.query_tree(root).expect("query failed");
+    assert_eq!(answer.best(), Some("x-content/video-dvd"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unreadable_subdir() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let db = embedded_mime_db();
+    let tmp = tempdir().expect("failed to create scratch dir");
+    let root = tmp.path();
+    fs::create_dir(root.join("dcim")).unwrap();
+    fs::set_permissions(root.join("dcim"), fs::Permissions::from_mode(0o000)).unwrap();
+    let answer = db.query_tree(root);
+    fs::set_permissions(root.join("dcim"), fs::Permissions::from_mode(0o755)).unwrap();
+    assert!(answer.expect("query failed").is_unknown());
+}
@@ -0,0 +1,53 @@
+use std::ffi::OsStr;
+
+use crate::{embedded_mime_db, FileQuery};
+
+#[test]
+fn test_svg_root() {
+    let db = embedded_mime_db();
+    let data = br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#;
+    assert_eq!(db.query_xml(data).best(), Some("image/svg+xml"));
+}
+
+#[test]
+fn test_mislabelled_svg() {
+    let db = embedded_mime_db();
+    let query = FileQuery::builder()
+        .filename(OsStr::new("drawing.xml"))
+        .content(
+            br#"<?xml version="1.0"?>
+<!-- exported by hand -->
+<svg xmlns="http://www.w3.org/2000/svg"><rect/></svg>"#,
+        )
+        .build();
+    let answer = db.query(&query).expect("query failed");
+    assert_eq!(answer.best(), Some("image/svg+xml"));
+}
+
+#[test]
+fn test_mislabelled_xhtml() {
+    let db = embedded_mime_db();
+    let query = FileQuery::builder()
+        .filename(OsStr::new("page.xml"))
+        .content(
+            br#"<?xml version="1.0"?>
+<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN"
+  "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd">
+<html xmlns="http://www.w3.org/1999/xhtml" lang="en">"#,
+        )
+        .build();
+    let answer = db.query(&query).expect("query failed");
+    assert_eq!(answer.best(), Some("application/xhtml+xml"));
+}
+
+#[test]
+fn test_unknown_root() {
+    let db = embedded_mime_db();
+    let query = FileQuery::builder()
+        .filename(OsStr::new("data.xml"))
+        .content(br#"<?xml version="1.0"?><hackem xmlns="urn:x-muche"/>"#)
+        .build();
+    let answer = db.query(&query).expect("query failed");
+    assert_eq!(answer.best(), Some("application/xml"));
+}
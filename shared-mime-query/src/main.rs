@@ -11,13 +11,13 @@ use anyhow::Result;
 use clap::{Args, Parser};
 use log::*;
 use serde_json::{to_string, to_writer_pretty};
-use shared_mime::FileQuery;
 use shared_mime::MimeDB;
 use stderrlog::StdErrLog;
 
 use shared_mime::load_mime_db as load_xdg_mime_db;
 use shared_mime::runtime::mimeinfo::load_xdg_mime_info;
 use shared_mime::runtime::parse_mime_package;
+use shared_mime::runtime::update_mime_database;
 use shared_mime::runtime::xdg_mime_search_dirs;
 #[cfg(feature = "embedded")]
 use shared_mime_embedded::{embedded_mime_db, load_mime_db as load_joint_mime_db};
@@ -49,6 +49,10 @@ pub struct CLI {
     #[arg(long = "json")]
     json: bool,
 
+    /// Explain how the type was found (with --type-of).
+    #[arg(long = "explain", requires = "type_of")]
+    explain: bool,
+
     /// Only use the embeded MIME db.
     #[arg(long = "no-runtime")]
     no_runtime: bool,
@@ -77,6 +81,10 @@ pub struct MIMEActions {
     #[arg(long = "dump-packages")]
     dump_packages: bool,
 
+    /// Regenerate the derived files in a MIME directory from its packages.
+    #[arg(long = "update-db")]
+    update_db: Option<PathBuf>,
+
     /// Query information about a type.
     #[arg(short = 'I', long = "type-info")]
     type_info: Option<String>,
@@ -106,6 +114,8 @@ fn main() -> Result<()> {
         cli.dump()
     } else if cli.action.dump_packages {
         cli.dump_packages()
+    } else if let Some(dir) = &cli.action.update_db {
+        cli.update_db(dir)
     } else if let Some(path) = &cli.action.type_of {
         cli.type_of(path)
     } else if let Some(typ) = &cli.action.type_info {
@@ -197,6 +207,12 @@ impl CLI {
         Ok(())
     }
 
+    fn update_db(&self, dir: &Path) -> Result<()> {
+        info!("updating MIME database in {}", dir.display());
+        update_mime_database(dir)?;
+        Ok(())
+    }
+
     fn open_text_output(&self) -> Result<Box<dyn Write>> {
         let out: Box<dyn Write> = if let Some(op) = &self.output {
             Box::new(
@@ -233,9 +249,21 @@ impl CLI {
     fn type_info(&self, name: &str) -> Result<()> {
         let db = self.load_db()?;
         info!("looking up type information for {}", name);
-        if let Some(desc) = db.description(name) {
+        if db.is_alias(name) {
+            println!("canonical type: {}", db.canonical(name));
+        }
+        if let Some(desc) = db.description_for_current_locale(name) {
             println!("description: {}", desc);
         }
+        if let Some(acronym) = db.acronym(name) {
+            if let Some(expanded) = db.expanded_acronym(name) {
+                println!("acronym: {} ({})", acronym, expanded);
+            } else {
+                println!("acronym: {}", acronym);
+            }
+        }
+        println!("icon: {}", db.icon_name(name));
+        println!("generic icon: {}", db.generic_icon_name(name));
         let aliases = db.aliases(name);
         if !aliases.is_empty() {
             println!("aliases: {}", aliases.join(", "))
@@ -254,8 +282,19 @@ impl CLI {
     fn type_of(&self, path: &Path) -> Result<()> {
         let db = self.load_db()?;
         info!("looking up type for {}", path.display());
-        let query = FileQuery::for_path(path)?;
-        let ans = db.query(&query)?;
+        let ans = if self.explain {
+            let exp = db.explain_path(path)?;
+            if self.json {
+                to_writer_pretty(self.open_text_output()?, &exp)?;
+                return Ok(());
+            }
+            for step in &exp.steps {
+                println!("{}: {}", path.display(), step);
+            }
+            exp.answer
+        } else {
+            db.query_path(path)?.into_owned()
+        };
         let all = ans.all_types();
         if let Some(mt) = ans.best() {
             println!("{}: {}", path.display(), mt);
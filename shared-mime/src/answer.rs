@@ -1,10 +1,75 @@
+//! Results of MIME type lookups.
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
 /// Result of looking up a MIME type.
 #[derive(Debug, Clone)]
 pub struct Answer<'a> {
     types: Vec<&'a str>,
+    found: Vec<Found<'a>>,
     ambiguous: bool,
 }
 
+/// A type found by a lookup, as tracked while querying.
+#[derive(Debug, Clone)]
+pub(crate) struct Found<'a> {
+    pub mimetype: &'a str,
+    pub source: MatchSource<'a>,
+    pub origin: Option<&'a str>,
+}
+
+/// A type found by a lookup, with the reason it was found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candidate<'a> {
+    /// The MIME type.
+    pub mimetype: Cow<'a, str>,
+    /// The rule that matched.
+    pub source: MatchSource<'a>,
+    /// The package (or other source of records) that defined the rule, if known.
+    pub origin: Option<Cow<'a, str>>,
+}
+
+/// The kind of rule that identified a type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum MatchSource<'a> {
+    /// File metadata (for `inode/*` types).
+    Metadata,
+    /// A file name glob.
+    Glob { pattern: Cow<'a, str>, weight: i32 },
+    /// A magic (content) rule, identified by the offset and value of its
+    /// top-level match that matched (with host byte order applied).
+    Magic {
+        priority: u32,
+        offset: usize,
+        value: Cow<'a, [u8]>,
+    },
+    /// A tree magic rule.
+    TreeMagic { priority: u32 },
+    /// An XML document's root element.
+    RootXml {
+        namespace: Cow<'a, str>,
+        local_name: Cow<'a, str>,
+    },
+    /// The content looks like text.
+    Text,
+    /// The file is empty.
+    ZeroSize,
+    /// Nothing else matched.
+    Default,
+}
+
+/// An [Answer] that does not borrow from the database, so it can be stored,
+/// sent between threads, or serialized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnedAnswer {
+    /// The matching types, best first.
+    pub candidates: Vec<Candidate<'static>>,
+    /// Whether the matching types are ambiguous.
+    pub ambiguous: bool,
+}
+
 impl Answer<'static> {
     pub(crate) fn unknown() -> Answer<'static> {
         Self::new(vec![], false)
@@ -12,17 +77,19 @@ impl Answer<'static> {
 }
 
 impl<'a> Answer<'a> {
-    pub(crate) fn new(types: Vec<&'a str>, ambiguous: bool) -> Answer<'a> {
-        Answer { types, ambiguous }
-    }
-
-    pub(crate) fn definite(name: &'a str) -> Answer<'a> {
+    pub(crate) fn new(found: Vec<Found<'a>>, ambiguous: bool) -> Answer<'a> {
+        let types = found.iter().map(|f| f.mimetype).collect();
         Answer {
-            types: vec![name],
-            ambiguous: false,
+            types,
+            found,
+            ambiguous,
         }
     }
 
+    pub(crate) fn definite(name: &'a str, source: MatchSource<'a>) -> Answer<'a> {
+        Self::new(vec![Found::new(name, source, None)], false)
+    }
+
     /// Query whether this answer is definite (resolved to a single, known type).
     pub fn is_definite(&self) -> bool {
         !self.types.is_empty() && !self.ambiguous
@@ -51,4 +118,167 @@ impl<'a> Answer<'a> {
     pub fn all_types(&self) -> &'_ [&'a str] {
         &self.types
     }
+
+    /// Get the matching types with the rules that found them.
+    pub fn candidates(&self) -> Vec<Candidate<'a>> {
+        self.found.iter().cloned().map(Candidate::from).collect()
+    }
+
+    pub(crate) fn found(&self) -> &[Found<'a>] {
+        &self.found
+    }
+
+    pub(crate) fn into_found(self) -> Vec<Found<'a>> {
+        self.found
+    }
+
+    /// Copy this answer so it no longer borrows from the database.
+    pub fn into_owned(self) -> OwnedAnswer {
+        OwnedAnswer {
+            candidates: self
+                .found
+                .into_iter()
+                .map(|f| Candidate::from(f).into_owned())
+                .collect(),
+            ambiguous: self.ambiguous,
+        }
+    }
+}
+
+impl<'a> Found<'a> {
+    pub(crate) fn new(
+        mimetype: &'a str,
+        source: MatchSource<'a>,
+        origin: Option<&'a str>,
+    ) -> Found<'a> {
+        Found {
+            mimetype,
+            source,
+            origin,
+        }
+    }
+}
+
+impl<'a> From<Found<'a>> for Candidate<'a> {
+    fn from(found: Found<'a>) -> Self {
+        Candidate {
+            mimetype: Cow::Borrowed(found.mimetype),
+            source: found.source,
+            origin: found.origin.map(Cow::Borrowed),
+        }
+    }
+}
+
+impl Candidate<'_> {
+    /// Copy this candidate so it no longer borrows from the database.
+    pub fn into_owned(self) -> Candidate<'static> {
+        Candidate {
+            mimetype: Cow::Owned(self.mimetype.into_owned()),
+            source: self.source.into_owned(),
+            origin: self.origin.map(|o| Cow::Owned(o.into_owned())),
+        }
+    }
+}
+
+impl MatchSource<'_> {
+    /// Copy this source so it no longer borrows from the database.
+    pub fn into_owned(self) -> MatchSource<'static> {
+        let own = |s: Cow<'_, str>| Cow::Owned(s.into_owned());
+        match self {
+            MatchSource::Metadata => MatchSource::Metadata,
+            MatchSource::Glob { pattern, weight } => MatchSource::Glob {
+                pattern: own(pattern),
+                weight,
+            },
+            MatchSource::Magic {
+                priority,
+                offset,
+                value,
+            } => MatchSource::Magic {
+                priority,
+                offset,
+                value: Cow::Owned(value.into_owned()),
+            },
+            MatchSource::TreeMagic { priority } => MatchSource::TreeMagic { priority },
+            MatchSource::RootXml {
+                namespace,
+                local_name,
+            } => MatchSource::RootXml {
+                namespace: own(namespace),
+                local_name: own(local_name),
+            },
+            MatchSource::Text => MatchSource::Text,
+            MatchSource::ZeroSize => MatchSource::ZeroSize,
+            MatchSource::Default => MatchSource::Default,
+        }
+    }
+}
+
+impl OwnedAnswer {
+    /// Query whether this answer is definite (resolved to a single, known type).
+    pub fn is_definite(&self) -> bool {
+        !self.candidates.is_empty() && !self.ambiguous
+    }
+
+    /// Query whether this answer is unknown (no resulting types).
+    pub fn is_unknown(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    /// Query whether this answer is ambiguous (multiple matching types).
+    pub fn is_ambiguous(&self) -> bool {
+        self.ambiguous
+    }
+
+    /// Get the best type, if known.  Returns [None] when no type is found or the type is ambiguous.
+    pub fn best(&self) -> Option<&str> {
+        if self.ambiguous {
+            None
+        } else {
+            self.candidates.first().map(|c| c.mimetype.as_ref())
+        }
+    }
+
+    /// Get all matching types.
+    pub fn all_types(&self) -> Vec<&str> {
+        self.candidates
+            .iter()
+            .map(|c| c.mimetype.as_ref())
+            .collect()
+    }
+}
+
+impl From<Answer<'_>> for OwnedAnswer {
+    fn from(answer: Answer<'_>) -> Self {
+        answer.into_owned()
+    }
+}
+
+#[test]
+fn test_owned_answer_serde() {
+    let answer = OwnedAnswer {
+        candidates: vec![
+            Candidate {
+                mimetype: "application/x-hackem".into(),
+                source: MatchSource::Magic {
+                    priority: 50,
+                    offset: 0,
+                    value: Cow::Borrowed(b"HKM!"),
+                },
+                origin: Some("hackem.xml".into()),
+            },
+            Candidate {
+                mimetype: "text/x-rogue-save".into(),
+                source: MatchSource::Glob {
+                    pattern: "*.sav".into(),
+                    weight: 50,
+                },
+                origin: None,
+            },
+        ],
+        ambiguous: true,
+    };
+    let json = serde_json::to_string(&answer).unwrap();
+    let back: OwnedAnswer = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, answer);
 }
@@ -1,8 +1,14 @@
 //! Glob-based filename matching utility.
 //!
-//! This is like `fnmatch`, matching glob patterns. It does not treat '/'
-//! specially, as it is just intended for final file names.
+//! This is like `fnmatch`, matching glob patterns (`*`, `?`, bracket
+//! expressions like `[a-z]` and `[!0-9]`, and backslash escapes). It does not
+//! treat '/' specially, as it is just intended for final file names.
+//!
+//! Patterns are matched byte by byte, so bracket expressions only work with
+//! ASCII characters; everything else supports UTF-8 names, including
+//! case-insensitive matching.
 
+use std::borrow::Cow;
 use std::mem::replace;
 
 /// Match files against a pattern.
@@ -15,9 +21,9 @@ pub struct FileMatcher {
 /// A match rule for matching files.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum MatchRule {
-    Literal(Vec<u8>),
-    Suffix(Vec<u8>),
-    Pattern(Vec<MatchElement>),
+    Literal(Cow<'static, [u8]>),
+    Suffix(Cow<'static, [u8]>),
+    Pattern(Cow<'static, [MatchElement]>),
 }
 
 /// Single elements of a pattern.
@@ -25,22 +31,34 @@ pub enum MatchRule {
 pub enum MatchElement {
     Star,
     Wildcard,
-    Range(u8, u8),
-    Literal(Vec<u8>),
+    /// A bracket expression, matching one byte in (or, if negated, not in)
+    /// any of the inclusive ranges.
+    ///
+    /// As sets match single bytes, only ASCII members are useful: a set of
+    /// multi-byte UTF-8 characters such as `[а-я]` matches bytes, not
+    /// characters, and is not case-folded.
+    Set {
+        negated: bool,
+        ranges: Cow<'static, [(u8, u8)]>,
+    },
+    Literal(Cow<'static, [u8]>),
 }
 
 impl FileMatcher {
+    /// Create a case-insensitive matcher.
     pub fn new<S: AsRef<str>>(pattern: S) -> FileMatcher {
+        let pattern = fold_case(pattern.as_ref().as_bytes());
         FileMatcher {
-            rule: pattern.as_ref().into(),
+            rule: String::from_utf8_lossy(&pattern).as_ref().into(),
             case_sensitive: false,
         }
     }
 
-    pub fn case_sensitive(self) -> FileMatcher {
+    /// Create a case-sensitive matcher.
+    pub fn new_case_sensitive<S: AsRef<str>>(pattern: S) -> FileMatcher {
         FileMatcher {
+            rule: pattern.as_ref().into(),
             case_sensitive: true,
-            ..self
         }
     }
 
@@ -51,24 +69,79 @@ impl FileMatcher {
             self.rule.matches(path)
         }
     }
+
+    /// Match a path, given both the path and its [fold_case]d form.
+    ///
+    /// This saves folding the path again for every matcher it is checked
+    /// against.
+    pub fn matches_folded(&self, path: &[u8], folded: &[u8]) -> bool {
+        if self.case_sensitive {
+            self.rule.matches_with_case(path)
+        } else {
+            self.rule.matches_ascii_fold(folded)
+        }
+    }
+}
+
+/// Fold the case of a file name or pattern, for case-insensitive matching.
+///
+/// UTF-8 text is lowercased character by character (without the contextual
+/// rules of [str::to_lowercase], so folding a suffix gives a suffix of the
+/// folded name).  Other byte strings only have their ASCII letters folded.
+pub fn fold_case(name: &[u8]) -> Vec<u8> {
+    if name.is_ascii() {
+        return name.to_ascii_lowercase();
+    }
+    match std::str::from_utf8(name) {
+        Ok(s) => s
+            .chars()
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+            .into_bytes(),
+        Err(_) => name.to_ascii_lowercase(),
+    }
 }
 
 impl MatchRule {
+    /// Match a path, ignoring case.
+    ///
+    /// Non-ASCII case is only ignored if the rule's pattern has been folded
+    /// with [fold_case].
     pub fn matches(&self, path: &[u8]) -> bool {
+        if path.is_ascii() {
+            self.matches_ascii_fold(path)
+        } else {
+            self.matches_ascii_fold(&fold_case(path))
+        }
+    }
+
+    /// Match a path that has already been folded with [fold_case].
+    pub fn matches_ascii_fold(&self, path: &[u8]) -> bool {
         match self {
             MatchRule::Literal(lit) => lit.eq_ignore_ascii_case(path),
             MatchRule::Suffix(sfx) => {
                 sfx.eq_ignore_ascii_case(&path[path.len().saturating_sub(sfx.len())..])
             }
-            MatchRule::Pattern(pat) => seq_matches(pat, path),
+            MatchRule::Pattern(pat) => seq_matches(pat, path, true),
         }
     }
 
     pub fn matches_with_case(&self, path: &[u8]) -> bool {
         match self {
-            MatchRule::Literal(lit) => lit == path,
+            MatchRule::Literal(lit) => lit.as_ref() == path,
             MatchRule::Suffix(sfx) => path.ends_with(sfx),
-            MatchRule::Pattern(pat) => seq_matches_with_case(pat, path),
+            MatchRule::Pattern(pat) => seq_matches(pat, path, false),
+        }
+    }
+
+    /// Get the length of the pattern this rule was parsed from.
+    ///
+    /// Escapes are not counted, as they are not kept after parsing.
+    pub fn pattern_len(&self) -> usize {
+        match self {
+            MatchRule::Literal(lit) => lit.len(),
+            MatchRule::Suffix(sfx) => sfx.len() + 1,
+            MatchRule::Pattern(pat) => pat.iter().map(|e| e.pattern_len()).sum(),
         }
     }
 }
@@ -79,52 +152,239 @@ impl From<&str> for MatchRule {
         match &elts[..] {
             [MatchElement::Literal(lit)] => MatchRule::Literal(lit.clone()),
             [MatchElement::Star, MatchElement::Literal(sfx)] => MatchRule::Suffix(sfx.clone()),
-            _ => MatchRule::Pattern(elts),
+            _ => MatchRule::Pattern(elts.into()),
+        }
+    }
+}
+
+impl MatchElement {
+    fn pattern_len(&self) -> usize {
+        match self {
+            MatchElement::Star | MatchElement::Wildcard => 1,
+            MatchElement::Set { negated, ranges } => {
+                let members: usize = ranges.iter().map(|(s, e)| if s == e { 1 } else { 3 }).sum();
+                2 + *negated as usize + members
+            }
+            MatchElement::Literal(lit) => lit.len(),
+        }
+    }
+
+    /// Match a non-star element at the start of a path, returning the number
+    /// of bytes consumed.
+    fn match_prefix(&self, path: &[u8], fold: bool) -> Option<usize> {
+        match self {
+            MatchElement::Star => Some(0),
+            MatchElement::Wildcard => (!path.is_empty()).then_some(1),
+            MatchElement::Set { negated, ranges } => {
+                let c = *path.first()?;
+                let found = if fold {
+                    let c = c.to_ascii_lowercase();
+                    ranges
+                        .iter()
+                        .any(|(s, e)| c >= s.to_ascii_lowercase() && c <= e.to_ascii_lowercase())
+                } else {
+                    ranges.iter().any(|(s, e)| c >= *s && c <= *e)
+                };
+                (found != *negated).then_some(1)
+            }
+            MatchElement::Literal(lit) => {
+                let head = path.get(..lit.len())?;
+                let ok = if fold {
+                    lit.eq_ignore_ascii_case(head)
+                } else {
+                    lit.as_ref() == head
+                };
+                ok.then_some(lit.len())
+            }
         }
     }
 }
 
 /// Match a sequence of rules against a string.
 ///
-/// TODO: This is inefficient and recursive, can cause problems on very long
-/// paths and pathological patterns.
-fn seq_matches(pat: &[MatchElement], path: &[u8]) -> bool {
+/// Every element other than `*` matches a fixed number of bytes, so when an
+/// element fails to match it is enough to retry from the most recent `*` with
+/// one more byte consumed.  This takes at most `O(pattern × path)` steps.
+fn seq_matches(pat: &[MatchElement], path: &[u8], fold: bool) -> bool {
+    let mut pi = 0;
+    let mut si = 0;
+    // element after the last star, and the path position it is retried from
+    let mut backtrack = None;
+    loop {
+        if let Some(elt) = pat.get(pi) {
+            if *elt == MatchElement::Star {
+                pi += 1;
+                backtrack = Some((pi, si));
+                continue;
+            }
+            if let Some(n) = elt.match_prefix(&path[si..], fold) {
+                pi += 1;
+                si += n;
+                continue;
+            }
+        } else if si == path.len() {
+            return true;
+        }
+        match backtrack {
+            Some((bpi, bsi)) if bsi < path.len() => {
+                backtrack = Some((bpi, bsi + 1));
+                pi = bpi;
+                si = bsi + 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+fn parse_pattern(pattern: &[u8]) -> Vec<MatchElement> {
+    let n = pattern.len();
+    let mut elts = Vec::new();
+    let mut current = Vec::with_capacity(n);
+    let mut pos = 0;
+    while pos < n {
+        match pattern[pos] {
+            b'*' => {
+                maybe_push_literal(&mut elts, &mut current, n - pos);
+                // consecutive stars are redundant
+                if elts.last() != Some(&MatchElement::Star) {
+                    elts.push(MatchElement::Star)
+                }
+            }
+            b'?' => {
+                maybe_push_literal(&mut elts, &mut current, n - pos);
+                elts.push(MatchElement::Wildcard)
+            }
+            b'\\' if pos + 1 < n => {
+                pos += 1;
+                current.push(pattern[pos]);
+            }
+            b'[' => match parse_set(&pattern[pos + 1..]) {
+                Some((set, len)) => {
+                    maybe_push_literal(&mut elts, &mut current, n - pos);
+                    elts.push(set);
+                    pos += len;
+                }
+                // an unterminated bracket is a literal
+                None => current.push(b'['),
+            },
+            c => current.push(c),
+        }
+        pos += 1;
+    }
+    maybe_push_literal(&mut elts, &mut current, n - pos);
+    elts
+}
+
+/// Parse a bracket expression, after its opening `[`.
+///
+/// Returns the set and the number of bytes consumed (including the closing
+/// `]`), or `None` if the expression is not terminated.
+fn parse_set(pattern: &[u8]) -> Option<(MatchElement, usize)> {
+    let mut pos = 0;
+    let negated = matches!(pattern.first(), Some(b'!' | b'^'));
+    if negated {
+        pos += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let mut c = *pattern.get(pos)?;
+        // a leading ']' is a member, not the end
+        if c == b']' && !first {
+            break;
+        }
+        first = false;
+        if c == b'\\' {
+            pos += 1;
+            c = *pattern.get(pos)?;
+        }
+        pos += 1;
+        match (pattern.get(pos), pattern.get(pos + 1)) {
+            (Some(b'-'), Some(e)) if *e != b']' => {
+                let mut e = *e;
+                pos += 2;
+                if e == b'\\' {
+                    e = *pattern.get(pos)?;
+                    pos += 1;
+                }
+                ranges.push((c, e));
+            }
+            _ => ranges.push((c, c)),
+        }
+    }
+    let set = MatchElement::Set {
+        negated,
+        ranges: ranges.into(),
+    };
+    Some((set, pos + 1))
+}
+
+fn maybe_push_literal(elts: &mut Vec<MatchElement>, current: &mut Vec<u8>, n: usize) {
+    if !current.is_empty() {
+        elts.push(MatchElement::Literal(
+            replace(current, Vec::with_capacity(n)).into(),
+        ));
+    }
+}
+
+/// Reference matcher, for checking [seq_matches].
+#[cfg(test)]
+fn seq_matches_recursive(pat: &[MatchElement], path: &[u8], fold: bool) -> bool {
+    match pat {
+        [] => path.is_empty(),
+        [MatchElement::Star, rest @ ..] => {
+            (0..=path.len()).any(|i| seq_matches_recursive(rest, &path[i..], fold))
+        }
+        [elt, rest @ ..] => match elt.match_prefix(path, fold) {
+            Some(n) => seq_matches_recursive(rest, &path[n..], fold),
+            None => false,
+        },
+    }
+}
+
+/// The original recursive matchers, kept to check [seq_matches] against.
+///
+/// These only support `*`, `?` and single ranges (as `[a-z]`).
+#[cfg(test)]
+fn seq_matches_baseline(pat: &[MatchElement], path: &[u8]) -> bool {
     match pat {
         [] => path.is_empty(),
-        _ if pat.is_empty() => false,
         [MatchElement::Literal(lit)] => lit.eq_ignore_ascii_case(path),
         [MatchElement::Literal(lit), ..] => {
             path.len() >= lit.len()
                 && lit.eq_ignore_ascii_case(&path[0..lit.len()])
-                && seq_matches(&pat[1..], &path[lit.len()..])
+                && seq_matches_baseline(&pat[1..], &path[lit.len()..])
         }
         [MatchElement::Star] => true,
         [MatchElement::Star, ..] => {
             for i in 0..path.len() {
-                if seq_matches(&pat[1..], &path[i..]) {
+                if seq_matches_baseline(&pat[1..], &path[i..]) {
                     return true;
                 }
             }
             false
         }
-        [MatchElement::Wildcard, ..] => !path.is_empty() && seq_matches(&pat[1..], &path[1..]),
-        [MatchElement::Range(s, e), ..] => {
+        [MatchElement::Wildcard, ..] => {
+            !path.is_empty() && seq_matches_baseline(&pat[1..], &path[1..])
+        }
+        [set, ..] => {
+            let (s, e) = single_range(set);
             !path.is_empty()
                 && path[0].to_ascii_lowercase() >= s.to_ascii_lowercase()
                 && path[0].to_ascii_lowercase() <= e.to_ascii_lowercase()
-                && seq_matches(&pat[1..], &path[1..])
+                && seq_matches_baseline(&pat[1..], &path[1..])
         }
     }
 }
 
+#[cfg(test)]
 fn seq_matches_with_case(pat: &[MatchElement], path: &[u8]) -> bool {
     match pat {
         [] => path.is_empty(),
-        _ if pat.is_empty() => false,
-        [MatchElement::Literal(lit)] => lit == path,
+        [MatchElement::Literal(lit)] => lit.as_ref() == path,
         [MatchElement::Literal(lit), ..] => {
             path.len() >= lit.len()
-                && lit == &path[0..lit.len()]
+                && lit.as_ref() == &path[0..lit.len()]
                 && seq_matches_with_case(&pat[1..], &path[lit.len()..])
         }
         [MatchElement::Star] => true,
@@ -139,51 +399,34 @@ fn seq_matches_with_case(pat: &[MatchElement], path: &[u8]) -> bool {
         [MatchElement::Wildcard, ..] => {
             !path.is_empty() && seq_matches_with_case(&pat[1..], &path[1..])
         }
-        [MatchElement::Range(s, e), ..] => {
+        [set, ..] => {
+            let (s, e) = single_range(set);
             !path.is_empty()
-                && path[0] >= *s
-                && path[0] <= *e
+                && path[0] >= s
+                && path[0] <= e
                 && seq_matches_with_case(&pat[1..], &path[1..])
         }
     }
 }
 
-fn parse_pattern(pattern: &[u8]) -> Vec<MatchElement> {
-    let n = pattern.len();
-    let mut elts = Vec::new();
-    let mut current = Vec::with_capacity(n);
-    let mut pos = 0;
-    while pos < n {
-        match pattern[pos] {
-            b'*' => {
-                maybe_push_literal(&mut elts, &mut current, n - pos);
-                elts.push(MatchElement::Star)
-            }
-            b'?' => {
-                maybe_push_literal(&mut elts, &mut current, n - pos);
-                elts.push(MatchElement::Wildcard)
-            }
-            b'[' if n > pos + 4 && pattern[pos + 2] == b'-' && pattern[pos + 4] == b']' => {
-                maybe_push_literal(&mut elts, &mut current, n - pos);
-                let start = pattern[pos + 1];
-                let end = pattern[pos + 3];
-                pos += 4;
-                elts.push(MatchElement::Range(start, end));
-            }
-            c => current.push(c),
-        }
-        pos += 1;
+/// Get the range of a set with a single range, as the baseline matchers
+/// supported.
+#[cfg(test)]
+fn single_range(elt: &MatchElement) -> (u8, u8) {
+    match elt {
+        MatchElement::Set {
+            negated: false,
+            ranges,
+        } if ranges.len() == 1 => ranges[0],
+        _ => panic!("{:?} is not a single range", elt),
     }
-    maybe_push_literal(&mut elts, &mut current, n - pos);
-    elts
 }
 
-fn maybe_push_literal(elts: &mut Vec<MatchElement>, current: &mut Vec<u8>, n: usize) {
-    if !current.is_empty() {
-        elts.push(MatchElement::Literal(replace(
-            current,
-            Vec::with_capacity(n),
-        )));
+#[cfg(test)]
+fn range_set(ranges: &[(u8, u8)]) -> MatchElement {
+    MatchElement::Set {
+        negated: false,
+        ranges: ranges.to_vec().into(),
     }
 }
 
@@ -207,7 +450,7 @@ fn test_parse_prefix() {
     let pat = parse_pattern(b"eldib.*");
     assert_eq!(
         pat,
-        &[MatchElement::Literal("eldib.".into()), MatchElement::Star]
+        &[MatchElement::Literal(b"eldib.".into()), MatchElement::Star]
     )
 }
 
@@ -217,9 +460,9 @@ fn test_parse_star_middle() {
     assert_eq!(
         pat,
         &[
-            MatchElement::Literal("xixaxa.".into()),
+            MatchElement::Literal(b"xixaxa.".into()),
             MatchElement::Star,
-            MatchElement::Literal(".xuxaxa".into())
+            MatchElement::Literal(b".xuxaxa".into())
         ]
     )
 }
@@ -229,7 +472,10 @@ fn test_parse_wildcard() {
     let pat = parse_pattern(b"man.?");
     assert_eq!(
         pat,
-        &[MatchElement::Literal("man.".into()), MatchElement::Wildcard,]
+        &[
+            MatchElement::Literal(b"man.".into()),
+            MatchElement::Wildcard,
+        ]
     )
 }
 
@@ -240,7 +486,7 @@ fn test_parse_star_wildcard() {
         pat,
         &[
             MatchElement::Star,
-            MatchElement::Literal(".".into()),
+            MatchElement::Literal(b".".into()),
             MatchElement::Wildcard,
         ]
     )
@@ -253,8 +499,8 @@ fn test_parse_range() {
         pat,
         &[
             MatchElement::Star,
-            MatchElement::Literal(".so.".into()),
-            MatchElement::Range(b'0', b'9'),
+            MatchElement::Literal(b".so.".into()),
+            range_set(&[(b'0', b'9')]),
         ]
     )
 }
@@ -266,9 +512,9 @@ fn test_parse_range_not_end() {
         pat,
         &[
             MatchElement::Star,
-            MatchElement::Literal(".so.".into()),
-            MatchElement::Range(b'0', b'9'),
-            MatchElement::Literal(".gz".into()),
+            MatchElement::Literal(b".so.".into()),
+            range_set(&[(b'0', b'9')]),
+            MatchElement::Literal(b".gz".into()),
         ]
     )
 }
@@ -284,7 +530,7 @@ fn test_basic_literal() {
 
 #[test]
 fn test_basic_lit_cs() {
-    let pat = FileMatcher::new("hackem.muche").case_sensitive();
+    let pat = FileMatcher::new_case_sensitive("hackem.muche");
     assert!(pat.matches(b"hackem.muche"));
     assert!(!pat.matches(b"HACKEM.muche"));
     assert!(!pat.matches(b"foobie.muche"))
@@ -302,7 +548,7 @@ fn test_basic_star_suffix() {
 
 #[test]
 fn test_basic_star_suffix_cs() {
-    let pat = FileMatcher::new("*.muche").case_sensitive();
+    let pat = FileMatcher::new_case_sensitive("*.muche");
     assert!(pat.matches(b"hackem.muche"));
     assert!(pat.matches(b"HACKEM.muche"));
     assert!(!pat.matches(b"hackem.MuChe"));
@@ -320,7 +566,7 @@ fn test_basic_star_prefix() {
 
 #[test]
 fn test_basic_star_prefix_cs() {
-    let pat = FileMatcher::new("foobie.*").case_sensitive();
+    let pat = FileMatcher::new_case_sensitive("foobie.*");
     assert!(pat.matches(b"foobie.muche"));
     assert!(pat.matches(b"foobie."));
     assert!(!pat.matches(b"FOOBIE.muche"));
@@ -336,8 +582,197 @@ fn test_star_middle() {
 
 #[test]
 fn test_class_match() {
-    let pat = FileMatcher::new("*.so.[0-9]").case_sensitive();
+    let pat = FileMatcher::new_case_sensitive("*.so.[0-9]");
     assert!(pat.matches(b"libc.so.6"));
     assert!(!pat.matches(b"libc.so.X"));
     assert!(!pat.matches(b"libc.sq.7"));
 }
+
+#[test]
+fn test_parse_set() {
+    let pat = parse_pattern(b"*.[ch]");
+    assert_eq!(
+        pat,
+        &[
+            MatchElement::Star,
+            MatchElement::Literal(b".".into()),
+            range_set(&[(b'c', b'c'), (b'h', b'h')]),
+        ]
+    )
+}
+
+#[test]
+fn test_parse_negated_ranges() {
+    let pat = parse_pattern(b"[!0-9a-f]");
+    assert_eq!(
+        pat,
+        &[MatchElement::Set {
+            negated: true,
+            ranges: vec![(b'0', b'9'), (b'a', b'f')].into(),
+        }]
+    )
+}
+
+#[test]
+fn test_parse_set_bracket() {
+    let pat = parse_pattern(b"[]-]");
+    assert_eq!(pat, &[range_set(&[(b']', b']'), (b'-', b'-')])])
+}
+
+#[test]
+fn test_parse_unterminated_set() {
+    let pat = parse_pattern(b"*.[ch");
+    assert_eq!(
+        pat,
+        &[MatchElement::Star, MatchElement::Literal(b".[ch".into())]
+    )
+}
+
+#[test]
+fn test_parse_escape() {
+    let pat = FileMatcher::new("\\*.\\[x]");
+    assert_eq!(pat.rule, MatchRule::Literal(b"*.[x]".into()));
+    assert!(pat.matches(b"*.[x]"));
+    assert!(!pat.matches(b"a.[x]"));
+}
+
+#[test]
+fn test_set_match() {
+    let pat = FileMatcher::new("*.[ch]pp");
+    assert!(pat.matches(b"x.cpp"));
+    assert!(pat.matches(b"x.HPP"));
+    assert!(!pat.matches(b"x.opp"));
+
+    let pat = FileMatcher::new_case_sensitive("*.[!0-9]");
+    assert!(pat.matches(b"x.c"));
+    assert!(!pat.matches(b"x.7"));
+    assert!(!pat.matches(b"x."));
+}
+
+#[test]
+fn test_pathological_match() {
+    let pat = FileMatcher::new("*a*a*a*a*a*a*a*a*a*a*b");
+    let name = vec![b'a'; 4096];
+    assert!(!pat.matches(&name));
+}
+
+/// Compare the iterative matcher with the recursive one on random patterns.
+#[test]
+fn test_fuzz_matches_recursive() {
+    // xorshift, so the test is reproducible without extra dependencies
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move |n: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % n as u64) as usize
+    };
+    const PAT_CHARS: &[u8] = b"abAB.*?[]!-\\";
+    const PATH_CHARS: &[u8] = b"abAB.-]";
+    for _ in 0..20000 {
+        let pat: Vec<u8> = (0..next(10))
+            .map(|_| PAT_CHARS[next(PAT_CHARS.len())])
+            .collect();
+        let path: Vec<u8> = (0..next(12))
+            .map(|_| PATH_CHARS[next(PATH_CHARS.len())])
+            .collect();
+        let elts = parse_pattern(&pat);
+        for fold in [false, true] {
+            assert_eq!(
+                seq_matches(&elts, &path, fold),
+                seq_matches_recursive(&elts, &path, fold),
+                "pattern {:?} on {:?} (fold {})",
+                String::from_utf8_lossy(&pat),
+                String::from_utf8_lossy(&path),
+                fold
+            );
+        }
+    }
+}
+
+/// Compare the iterative matcher with the original matchers, on random
+/// patterns they both support.
+#[test]
+fn test_fuzz_matches_baseline() {
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut next = move |n: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % n as u64) as usize
+    };
+    const PAT_TOKENS: &[&str] = &["a", "b", "A", "B", ".", "*", "?", "[a-b]", "[A-b]", "[.-a]"];
+    const PATH_CHARS: &[u8] = b"abAB.-";
+    for _ in 0..20000 {
+        let pat: String = (0..next(8))
+            .map(|_| PAT_TOKENS[next(PAT_TOKENS.len())])
+            .collect();
+        let path: Vec<u8> = (0..next(12))
+            .map(|_| PATH_CHARS[next(PATH_CHARS.len())])
+            .collect();
+        let elts = parse_pattern(pat.as_bytes());
+        assert_eq!(
+            seq_matches(&elts, &path, true),
+            seq_matches_baseline(&elts, &path),
+            "pattern {:?} on {:?}",
+            pat,
+            String::from_utf8_lossy(&path),
+        );
+        assert_eq!(
+            seq_matches(&elts, &path, false),
+            seq_matches_with_case(&elts, &path),
+            "pattern {:?} on {:?} (case-sensitive)",
+            pat,
+            String::from_utf8_lossy(&path),
+        );
+    }
+}
+
+#[test]
+fn test_fold_case() {
+    assert_eq!(fold_case(b"HACKEM.Muche"), b"hackem.muche");
+    assert_eq!(
+        fold_case("ДОКУМЕНТ.ΣΑΣ".as_bytes()),
+        "документ.σασ".as_bytes()
+    );
+    assert_eq!(fold_case(b"\xffFOO"), b"\xfffoo");
+}
+
+#[test]
+fn test_unicode_case() {
+    let pat = FileMatcher::new("*.ДОК");
+    assert_eq!(pat.rule, MatchRule::Suffix(".док".as_bytes().into()));
+    assert!(pat.matches("отчёт.док".as_bytes()));
+    assert!(pat.matches("ОТЧЁТ.ДОК".as_bytes()));
+    assert!(pat.matches("отчёт.Док".as_bytes()));
+    assert!(!pat.matches("отчёт.дак".as_bytes()));
+
+    let pat = FileMatcher::new("Ελληνικά-*.txt");
+    assert!(pat.matches("ΕΛΛΗΝΙΚΆ-1.TXT".as_bytes()));
+
+    let pat = FileMatcher::new_case_sensitive("*.ДОК");
+    assert!(!pat.matches("отчёт.док".as_bytes()));
+}
+
+#[test]
+fn test_matches_folded() {
+    let pat = FileMatcher::new("*.ДОК");
+    let name = "ОТЧЁТ.ДОК".as_bytes();
+    assert!(pat.matches_folded(name, &fold_case(name)));
+
+    let pat = FileMatcher::new_case_sensitive("*.ДОК");
+    assert!(pat.matches_folded(name, &fold_case(name)));
+    let name = "отчёт.док".as_bytes();
+    assert!(!pat.matches_folded(name, &fold_case(name)));
+
+    // sets are byte-wise, so a Cyrillic range does not match a letter
+    let pat = FileMatcher::new("*.[а-я]");
+    assert!(!pat.matches("отчёт.д".as_bytes()));
+}
+
+#[test]
+fn test_non_utf8_case() {
+    let pat = FileMatcher::new("*.txt");
+    assert!(pat.matches(b"\xff\xfe.TXT"));
+    assert!(!pat.matches(b"\xff\xfe.TXU"));
+}
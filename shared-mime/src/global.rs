@@ -0,0 +1,141 @@
+//! Process-wide default MIME database.
+//!
+//! The database is loaded with [load_mime_db](crate::load_mime_db) the first
+//! time it is needed, and shared for the rest of the process.  It can be
+//! replaced at any time with [set_global]; queries already in progress keep
+//! using the database they started with.
+use std::ffi::OsStr;
+use std::path::Path;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use log::*;
+
+use crate::{load_mime_db, FileQuery, MimeDB, OwnedAnswer, QueryError};
+
+static GLOBAL_DB: GlobalDb = GlobalDb::new(default_db);
+
+/// A lazily-loaded, replaceable MIME database shared across a process.
+///
+/// The loader is called the first time the database is needed (and again if
+/// it is [cleared](GlobalDb::clear)).  Crates that ship their own database
+/// can define a static `GlobalDb` with their own loader.
+pub struct GlobalDb {
+    db: RwLock<Option<Arc<MimeDB>>>,
+    loader: fn() -> MimeDB,
+}
+
+impl GlobalDb {
+    /// Create a global database that is loaded with `loader`.
+    pub const fn new(loader: fn() -> MimeDB) -> GlobalDb {
+        GlobalDb {
+            db: RwLock::new(None),
+            loader,
+        }
+    }
+
+    /// Get the database, loading it if necessary.
+    pub fn get(&self) -> Arc<MimeDB> {
+        if let Some(db) = &*self.db.read().unwrap_or_else(PoisonError::into_inner) {
+            return db.clone();
+        }
+        let mut slot = self.db.write().unwrap_or_else(PoisonError::into_inner);
+        // another thread may have loaded it while we waited for the lock
+        slot.get_or_insert_with(|| Arc::new((self.loader)()))
+            .clone()
+    }
+
+    /// Replace the database, returning the previous one (if it was loaded).
+    pub fn set(&self, db: MimeDB) -> Option<Arc<MimeDB>> {
+        let mut slot = self.db.write().unwrap_or_else(PoisonError::into_inner);
+        slot.replace(Arc::new(db))
+    }
+
+    /// Discard the database, so the next use reloads it.
+    pub fn clear(&self) -> Option<Arc<MimeDB>> {
+        let mut slot = self.db.write().unwrap_or_else(PoisonError::into_inner);
+        slot.take()
+    }
+
+    /// Guess the type of a file.
+    pub fn guess_for_path<P: AsRef<Path>>(&self, path: P) -> Result<OwnedAnswer, QueryError> {
+        Ok(self.get().query_path(path)?.into_owned())
+    }
+
+    /// Guess the type of a file from its name.
+    pub fn guess_for_name<S: AsRef<OsStr>>(&self, name: S) -> OwnedAnswer {
+        self.get().query_filename(name).into_owned()
+    }
+
+    /// Guess the type of a file from its content.
+    pub fn guess_for_bytes(&self, data: &[u8]) -> OwnedAnswer {
+        let query = FileQuery::builder().content(data).build();
+        self.get()
+            .query(&query)
+            .expect("content-only queries do not fail")
+            .into_owned()
+    }
+}
+
+fn default_db() -> MimeDB {
+    match load_mime_db() {
+        Ok(db) => db,
+        Err(e) => {
+            warn!("error loading MIME database: {}", e);
+            MimeDB::new()
+        }
+    }
+}
+
+/// Get the global MIME database, loading it if necessary.
+///
+/// If the database cannot be loaded, an empty database is used.
+pub fn global() -> Arc<MimeDB> {
+    GLOBAL_DB.get()
+}
+
+/// Replace the global MIME database, returning the previous one.
+pub fn set_global(db: MimeDB) -> Option<Arc<MimeDB>> {
+    GLOBAL_DB.set(db)
+}
+
+/// Guess the type of a file using the global database.
+pub fn guess_for_path<P: AsRef<Path>>(path: P) -> Result<OwnedAnswer, QueryError> {
+    GLOBAL_DB.guess_for_path(path)
+}
+
+/// Guess the type of a file from its name using the global database.
+pub fn guess_for_name<S: AsRef<OsStr>>(name: S) -> OwnedAnswer {
+    GLOBAL_DB.guess_for_name(name)
+}
+
+/// Guess the type of a file from its content using the global database.
+pub fn guess_for_bytes(data: &[u8]) -> OwnedAnswer {
+    GLOBAL_DB.guess_for_bytes(data)
+}
+
+#[test]
+fn test_set_global() {
+    use crate::record::glob_record;
+
+    let mut db = MimeDB::new();
+    db.add_records(vec![glob_record("text/x-hackem", &[("*.hkm", 50)])]);
+    set_global(db);
+    assert_eq!(guess_for_name("muche.hkm").best(), Some("text/x-hackem"));
+    assert_eq!(
+        guess_for_bytes(b"hackem muche\n").best(),
+        Some("text/plain")
+    );
+    let old = set_global(MimeDB::new()).unwrap();
+    assert_eq!(old.type_count(), 1);
+    assert!(guess_for_name("muche.hkm").is_unknown());
+}
+
+#[test]
+fn test_global_db_reload() {
+    static DB: GlobalDb = GlobalDb::new(MimeDB::new);
+
+    let db = DB.get();
+    assert!(Arc::ptr_eq(&db, &DB.get()));
+    assert!(DB.clear().is_some());
+    assert!(!Arc::ptr_eq(&db, &DB.get()));
+}
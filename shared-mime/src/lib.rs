@@ -2,6 +2,9 @@
 pub mod answer;
 pub mod error;
 mod fnmatch;
+mod global;
+mod locale;
+mod magic;
 pub mod mimedb;
 pub mod query;
 pub mod record;
@@ -9,14 +12,32 @@ pub mod record;
 pub mod runtime;
 mod search_queue;
 mod strcache;
+mod table;
+pub mod text;
+mod xml;
 
-use log::*;
+/// Compiled database types, for precompiled databases generated by
+/// [MimeDB::write_static].  These are not a stable interface.
+#[doc(hidden)]
+pub mod index {
+    pub use crate::fnmatch::{FileMatcher, MatchElement, MatchRule};
+    pub use crate::magic::{MagicMatcher, Matchlet};
+    pub use crate::mimedb::{
+        GlobIndex, GlobRule, MagicRule, StaticIndex, SuffixNode, TreeMagicRule, TreeMatcher,
+        TypeInfo,
+    };
+    pub use crate::record::TreeFileType;
+    pub use crate::strcache::CachedString;
+    pub use crate::table::Table;
+}
 
 pub use error::{LoadError, QueryError};
 
-pub use answer::Answer;
-pub use mimedb::MimeDB;
+pub use answer::{Answer, Candidate, MatchSource, OwnedAnswer};
+pub use global::{global, guess_for_bytes, guess_for_name, guess_for_path, set_global, GlobalDb};
+pub use mimedb::{ExplainStep, Explanation, MimeDB};
 pub use query::{FileQuery, FileQueryBuilder};
+pub use text::TextRules;
 
 /// Load the MIME database.
 #[cfg(not(feature = "xdg-runtime"))]
@@ -25,17 +46,14 @@ pub fn load_mime_db() -> Result<MimeDB, LoadError> {
 }
 
 /// Load the MIME database.
+///
+/// Package files that cannot be parsed are skipped with a warning.
 #[cfg(feature = "xdg-runtime")]
 pub fn load_mime_db() -> Result<MimeDB, LoadError> {
-    use runtime::load_xdg_mime_info;
+    use runtime::{LoadMode, MimeDbLoader};
 
-    let mut db = MimeDB::new();
-    let info = load_xdg_mime_info()?;
-    db.add_shared_mime_info(info);
-    debug!(
-        "loaded shared MIME info with {} types and {} globs",
-        db.type_count(),
-        db.glob_count()
-    );
-    Ok(db)
+    MimeDbLoader::new()
+        .mode(LoadMode::Lenient)
+        .xdg_dirs()
+        .load()
 }
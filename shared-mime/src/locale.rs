@@ -0,0 +1,95 @@
+//! Locale name handling for choosing translated strings.
+//!
+//! Locale names follow the POSIX form `lang_COUNTRY.CODESET@modifier`.  A
+//! translation is looked up by progressively stripping parts of the name,
+//! following the same order as gettext.
+
+use std::env;
+
+/// Expand a locale name into the translation languages to try, most specific first.
+///
+/// For example, `pt_BR.UTF-8` expands to `pt_BR` and `pt`.  The `C` and
+/// `POSIX` locales expand to nothing (the untranslated string is used).
+pub fn locale_fallbacks(locale: &str) -> Vec<String> {
+    let locale = locale.trim().replace('-', "_");
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return Vec::new();
+    }
+
+    let (rest, modifier) = match locale.split_once('@') {
+        Some((r, m)) => (r, Some(m)),
+        None => (locale.as_str(), None),
+    };
+    // the codeset does not matter for translation lookup
+    let rest = rest.split_once('.').map_or(rest, |(r, _)| r);
+    let (lang, country) = match rest.split_once('_') {
+        Some((l, c)) => (l, Some(c)),
+        None => (rest, None),
+    };
+    if lang == "C" || lang == "POSIX" {
+        return Vec::new();
+    }
+
+    let mut names = Vec::with_capacity(4);
+    if let Some(c) = country {
+        if let Some(m) = modifier {
+            names.push(format!("{}_{}@{}", lang, c, m));
+        }
+        names.push(format!("{}_{}", lang, c));
+    }
+    if let Some(m) = modifier {
+        names.push(format!("{}@{}", lang, m));
+    }
+    names.push(lang.to_string());
+    names
+}
+
+/// Get the translation languages for the current process, most preferred first.
+///
+/// This uses `LANGUAGE` (a colon-separated list), followed by the first of
+/// `LC_ALL`, `LC_MESSAGES` and `LANG` that is set.
+pub fn current_languages() -> Vec<String> {
+    let mut langs = Vec::new();
+    if let Some(list) = env::var_os("LANGUAGE") {
+        for lang in list.to_string_lossy().split(':') {
+            if !lang.is_empty() {
+                langs.push(lang.to_string());
+            }
+        }
+    }
+    for var in ["LC_ALL", "LC_MESSAGES", "LANG"] {
+        if let Some(val) = env::var_os(var) {
+            if !val.is_empty() {
+                langs.push(val.to_string_lossy().to_string());
+                break;
+            }
+        }
+    }
+    langs
+}
+
+#[test]
+fn test_fallback_simple() {
+    assert_eq!(locale_fallbacks("de"), vec!["de"]);
+}
+
+#[test]
+fn test_fallback_country() {
+    assert_eq!(locale_fallbacks("pt_BR"), vec!["pt_BR", "pt"]);
+    assert_eq!(locale_fallbacks("pt-BR"), vec!["pt_BR", "pt"]);
+}
+
+#[test]
+fn test_fallback_codeset_modifier() {
+    assert_eq!(
+        locale_fallbacks("sr_RS.UTF-8@latin"),
+        vec!["sr_RS@latin", "sr_RS", "sr@latin", "sr"]
+    );
+}
+
+#[test]
+fn test_fallback_c() {
+    assert!(locale_fallbacks("C").is_empty());
+    assert!(locale_fallbacks("C.UTF-8").is_empty());
+    assert!(locale_fallbacks("POSIX").is_empty());
+}
@@ -0,0 +1,160 @@
+//! Content-based (magic) matching.
+//!
+//! This evaluates the magic rules from the shared MIME database against a
+//! buffer containing the start of a file.
+
+use std::borrow::Cow;
+
+use crate::record::MagicMatch;
+
+/// Match file contents against a set of magic match trees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MagicMatcher {
+    pub matches: Cow<'static, [Matchlet]>,
+}
+
+/// A single compiled match, with host byte-order already applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Matchlet {
+    pub offset: usize,
+    pub range_length: usize,
+    /// The word size, for converting host-endian values to another byte order.
+    pub word_size: usize,
+    pub value: Cow<'static, [u8]>,
+    pub mask: Option<Cow<'static, [u8]>>,
+    pub children: Cow<'static, [Matchlet]>,
+}
+
+impl MagicMatcher {
+    pub fn new(matches: &[MagicMatch]) -> MagicMatcher {
+        MagicMatcher {
+            matches: matches.iter().map(Matchlet::from).collect(),
+        }
+    }
+
+    /// Check whether the data matches any of this matcher's match trees.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        self.find(data).is_some()
+    }
+
+    /// Find the first match tree that matches the data, returning its root.
+    pub fn find(&self, data: &[u8]) -> Option<&Matchlet> {
+        self.matches.iter().find(|m| m.matches(data))
+    }
+
+    /// Get the number of bytes needed to evaluate all matches.
+    pub fn extent(&self) -> usize {
+        self.matches.iter().map(|m| m.extent()).max().unwrap_or(0)
+    }
+}
+
+impl Matchlet {
+    fn matches(&self, data: &[u8]) -> bool {
+        let len = self.value.len();
+        let found = (self.offset..self.offset + self.range_length).any(|start| {
+            if let Some(window) = data.get(start..start + len) {
+                if let Some(mask) = &self.mask {
+                    window
+                        .iter()
+                        .zip(self.value.iter())
+                        .zip(mask.iter())
+                        .all(|((d, v), m)| d & m == v & m)
+                } else {
+                    window == self.value.as_ref()
+                }
+            } else {
+                false
+            }
+        });
+        found && (self.children.is_empty() || self.children.iter().any(|c| c.matches(data)))
+    }
+
+    fn extent(&self) -> usize {
+        let own = self.offset + self.range_length - 1 + self.value.len();
+        self.children
+            .iter()
+            .map(|c| c.extent())
+            .fold(own, |a, b| a.max(b))
+    }
+}
+
+impl From<&MagicMatch> for Matchlet {
+    fn from(m: &MagicMatch) -> Self {
+        let mut value = m.value.clone();
+        let mut mask = m.mask.clone();
+        // host-endian values are stored big-endian
+        let ws = m.word_size as usize;
+        if ws > 1 && cfg!(target_endian = "little") {
+            for chunk in value.chunks_mut(ws) {
+                chunk.reverse();
+            }
+            if let Some(mask) = &mut mask {
+                for chunk in mask.chunks_mut(ws) {
+                    chunk.reverse();
+                }
+            }
+        }
+        Matchlet {
+            offset: m.offset as usize,
+            range_length: m.range_length.max(1) as usize,
+            word_size: ws,
+            value: value.into(),
+            mask: mask.map(Cow::from),
+            children: m.children.iter().map(Matchlet::from).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+fn simple_match(offset: u32, range_length: u32, value: &[u8]) -> MagicMatch {
+    MagicMatch {
+        offset,
+        range_length,
+        word_size: 1,
+        value: value.to_vec(),
+        mask: None,
+        children: vec![],
+    }
+}
+
+#[test]
+fn test_match_prefix() {
+    let m = MagicMatcher::new(&[simple_match(0, 1, b"\x89PNG")]);
+    assert!(m.matches(b"\x89PNG\r\n\x1a\n"));
+    assert!(!m.matches(b"GIF89a"));
+    assert!(!m.matches(b"\x89PN"));
+}
+
+#[test]
+fn test_match_range() {
+    let m = MagicMatcher::new(&[simple_match(0, 8, b"<svg")]);
+    assert!(m.matches(b"   <svg>"));
+    assert!(!m.matches(b"          <svg>"));
+    assert_eq!(m.extent(), 11);
+}
+
+#[test]
+fn test_match_mask() {
+    let mut mm = simple_match(0, 1, b"BMxx");
+    mm.mask = Some(vec![0xff, 0xff, 0, 0]);
+    let m = MagicMatcher::new(&[mm]);
+    assert!(m.matches(b"BM\x12\x34"));
+    assert!(!m.matches(b"BN\x12\x34"));
+}
+
+#[test]
+fn test_match_children() {
+    let mut mm = simple_match(0, 1, b"RIFF");
+    mm.children.push(simple_match(8, 1, b"WAVE"));
+    let m = MagicMatcher::new(&[mm]);
+    assert!(m.matches(b"RIFF\0\0\0\0WAVE"));
+    assert!(!m.matches(b"RIFF\0\0\0\0AVI "));
+}
+
+#[test]
+fn test_match_host_endian() {
+    let mut mm = simple_match(0, 1, &[0xa1, 0xb2, 0xc3, 0xd4]);
+    mm.word_size = 4;
+    let m = MagicMatcher::new(&[mm]);
+    assert!(m.matches(&0xa1b2c3d4u32.to_ne_bytes()));
+}
@@ -1,43 +1,188 @@
+use std::borrow::Cow;
+use std::mem;
+
 use log::*;
 
-use super::{GlobRule, MimeDB};
+use super::{
+    GlobIndex, GlobRule, MagicRule, MimeDB, StaticIndex, TreeMagicRule, TreeMatcher, TypeInfo,
+};
 #[cfg(feature = "xdg-runtime")]
 use crate::runtime::mimeinfo::SharedMimeInfo;
-use crate::{fnmatch::FileMatcher, record::MimeTypeRecord};
+use crate::{
+    fnmatch::FileMatcher,
+    magic::MagicMatcher,
+    record::{MimeTypeRecord, TreeMatch},
+    strcache::StringCache,
+};
 
 impl MimeDB {
+    /// Add MIME type records to the database.
+    ///
+    /// Records added later take precedence over those added earlier.
     pub fn add_records(&mut self, records: Vec<MimeTypeRecord>) {
         self.sequence += 1;
+        self.add_sequence(records);
+    }
+
+    /// Add MIME type records to the database, recording where they came from.
+    ///
+    /// The origin (e.g. a package file name) is reported with query results.
+    pub fn add_records_from<S: Into<String>>(&mut self, origin: S, records: Vec<MimeTypeRecord>) {
+        self.sequence += 1;
+        self.origins
+            .to_mut()
+            .insert(self.sequence, origin.into().into());
+        self.add_sequence(records);
+    }
+
+    /// Add a precompiled database, taking precedence over the records
+    /// already in this database.
+    ///
+    /// The index's rules are copied with their sequences renumbered to follow
+    /// this database's.  Deletions were applied when the index was built, so
+    /// its `glob-deleteall` and `magic-deleteall` do not remove earlier rules.
+    pub fn add_static(&mut self, index: &'static StaticIndex) {
+        let base = self.sequence;
+        self.sequence += index.sequence;
+        for (seq, origin) in index.origins {
+            self.origins.to_mut().insert(base + seq, origin.clone());
+        }
+        self.globs
+            .to_mut()
+            .extend(index.globs.iter().map(|g| GlobRule {
+                sequence: base + g.sequence,
+                ..g.clone()
+            }));
+        self.magic
+            .to_mut()
+            .extend(index.magic.iter().map(|m| MagicRule {
+                sequence: base + m.sequence,
+                ..m.clone()
+            }));
+        self.treemagic
+            .to_mut()
+            .extend(index.treemagic.iter().map(|t| TreeMagicRule {
+                sequence: base + t.sequence,
+                ..t.clone()
+            }));
+        for (key, (mt, seq)) in index.root_xml {
+            self.root_xml
+                .to_mut()
+                .insert(key.clone(), (mt.clone(), base + seq));
+        }
+        // the rules are in place, so this merges the type details and
+        // rebuilds the indexes
+        let records = index
+            .types
+            .iter()
+            .map(|(name, info)| details_record(info, name))
+            .collect();
+        self.add_sequence(records);
+    }
+
+    fn add_sequence(&mut self, records: Vec<MimeTypeRecord>) {
+        let globs = self.globs.to_mut();
+        let magic = self.magic.to_mut();
+        let treemagic = self.treemagic.to_mut();
         for rec in records {
             let name = self.names.cache(&rec.name);
-            let info = self.type_info.entry(name).or_default();
-            if let Some(desc) = rec.description {
-                info.description = Some(desc);
-            }
-            info.aliases
-                .extend(rec.aliases.into_iter().map(|c| self.names.cache(c)));
-            info.parents
-                .extend(rec.superclasses.into_iter().map(|c| self.names.cache(c)));
+            let info = self.type_info.to_mut().entry(name.clone()).or_default();
+            // merge into a record with the type's details, and no rules, so
+            // the rules left in it are the new record's
+            let mut merged = details_record(info, &rec.name);
+            merged.merge(rec);
+            let mut rec = merged;
+            *info = TypeInfo {
+                description: rec.description.take().map(Cow::Owned),
+                localized_descriptions: mem::take(&mut rec.localized_descriptions)
+                    .into_iter()
+                    .map(|(l, d)| (l.into(), d.into()))
+                    .collect(),
+                icon: rec.icon.take().map(Cow::Owned),
+                generic_icon: rec.generic_icon.take().map(Cow::Owned),
+                acronym: rec.acronym.take().map(Cow::Owned),
+                expanded_acronym: rec.expanded_acronym.take().map(Cow::Owned),
+                aliases: rec.aliases.iter().map(|a| self.names.cache(a)).collect(),
+                parents: rec
+                    .superclasses
+                    .iter()
+                    .map(|p| self.names.cache(p))
+                    .collect(),
+            };
+            for alias in info.aliases.iter() {
+                self.aliases.to_mut().insert(alias.clone(), name.clone());
+            }
+            if rec.glob_deleteall {
+                debug!("{}: replacing previous globs", rec.name);
+                let seq = self.sequence;
+                globs.retain(|g| g.mimetype != name || g.sequence == seq);
+            }
             for glob in rec.globs {
-                let mut matcher = FileMatcher::new(glob.pattern);
-                if glob.case_sensitive {
-                    matcher = matcher.case_sensitive();
-                }
-                self.globs.push(GlobRule {
+                let matcher = if glob.case_sensitive {
+                    FileMatcher::new_case_sensitive(&glob.pattern)
+                } else {
+                    FileMatcher::new(&glob.pattern)
+                };
+                globs.push(GlobRule {
+                    pattern: glob.pattern.into(),
                     matcher,
                     sequence: self.sequence,
                     weight: glob.weight,
-                    mimetype: rec.name.clone(),
+                    mimetype: name.clone(),
+                })
+            }
+            if rec.magic_deleteall {
+                debug!("{}: replacing previous magic", rec.name);
+                let seq = self.sequence;
+                magic.retain(|m| m.mimetype != name || m.sequence == seq);
+            }
+            for rule in rec.magic {
+                magic.push(MagicRule {
+                    matcher: MagicMatcher::new(&rule.matches),
+                    sequence: self.sequence,
+                    priority: rule.priority,
+                    mimetype: name.clone(),
+                })
+            }
+            for rx in rec.root_xml {
+                let key = format!("{} {}", rx.namespace_uri, rx.local_name);
+                self.root_xml
+                    .to_mut()
+                    .insert(key.into(), (name.clone(), self.sequence));
+            }
+            for tm in rec.treemagic {
+                treemagic.push(TreeMagicRule {
+                    matches: tm
+                        .matches
+                        .iter()
+                        .map(|m| compile_tree(&mut self.names, m))
+                        .collect(),
+                    sequence: self.sequence,
+                    priority: tm.priority,
+                    mimetype: name.clone(),
                 })
             }
         }
-        self.globs.sort_by(|a, b| {
+        globs.sort_by(|a, b| {
             // higher sequences (later packages) go first
             let seq = a.sequence.cmp(&b.sequence).reverse();
             // higher weights go first
             let weight = a.weight.cmp(&b.weight).reverse();
             seq.then(weight)
         });
+        magic.sort_by(|a, b| {
+            // higher priorities go first, then later packages
+            let prio = a.priority.cmp(&b.priority).reverse();
+            let seq = a.sequence.cmp(&b.sequence).reverse();
+            prio.then(seq)
+        });
+        treemagic.sort_by(|a, b| {
+            let prio = a.priority.cmp(&b.priority).reverse();
+            let seq = a.sequence.cmp(&b.sequence).reverse();
+            prio.then(seq)
+        });
+        self.magic_extent = magic.iter().map(|m| m.matcher.extent()).max().unwrap_or(0);
+        self.glob_index = GlobIndex::build(globs);
     }
 
     #[cfg(feature = "xdg-runtime")]
@@ -45,8 +190,105 @@ impl MimeDB {
         for dir in info.directories {
             debug!("adding MIME info from {}", dir.path.display());
             for pkg in dir.packages {
-                self.add_records(pkg.types);
+                let origin = format!("{} ({})", dir.path.display(), pkg.filename);
+                self.add_records_from(origin, pkg.types);
             }
         }
     }
 }
+
+/// Get a record with a type's details (but not its rules).
+fn details_record(info: &TypeInfo, name: &str) -> MimeTypeRecord {
+    MimeTypeRecord {
+        name: name.into(),
+        description: info.description.as_ref().map(|d| d.to_string()),
+        localized_descriptions: info
+            .localized_descriptions
+            .iter()
+            .map(|(l, d)| (l.to_string(), d.to_string()))
+            .collect(),
+        icon: info.icon.as_ref().map(|i| i.to_string()),
+        generic_icon: info.generic_icon.as_ref().map(|i| i.to_string()),
+        acronym: info.acronym.as_ref().map(|a| a.to_string()),
+        expanded_acronym: info.expanded_acronym.as_ref().map(|a| a.to_string()),
+        aliases: info.aliases.iter().map(|a| a.to_string()).collect(),
+        superclasses: info.parents.iter().map(|p| p.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+fn compile_tree(names: &mut StringCache, tm: &TreeMatch) -> TreeMatcher {
+    TreeMatcher {
+        path: tm.path.clone().into(),
+        file_type: tm.file_type,
+        match_case: tm.match_case,
+        executable: tm.executable,
+        non_empty: tm.non_empty,
+        mimetype: tm.mimetype.as_ref().map(|mt| names.cache(mt)),
+        children: tm.children.iter().map(|c| compile_tree(names, c)).collect(),
+    }
+}
+
+#[cfg(test)]
+use crate::record::glob_record;
+
+#[test]
+fn test_layer_globs() {
+    let mut db = MimeDB::new();
+    db.add_records(vec![glob_record("text/x-hackem", &[("*.hkm", 50)])]);
+    db.add_records(vec![glob_record("text/x-hackem", &[("*.muche", 50)])]);
+    assert_eq!(db.glob_count(), 2);
+    assert_eq!(db.query_filename("a.hkm").best(), Some("text/x-hackem"));
+    assert_eq!(db.query_filename("a.muche").best(), Some("text/x-hackem"));
+}
+
+#[test]
+fn test_glob_deleteall() {
+    let mut db = MimeDB::new();
+    db.add_records(vec![
+        glob_record("text/x-hackem", &[("*.hkm", 50)]),
+        glob_record("text/x-foobie", &[("*.fb", 50)]),
+    ]);
+    let mut rec = glob_record("text/x-hackem", &[("*.muche", 50)]);
+    rec.glob_deleteall = true;
+    db.add_records(vec![rec]);
+    assert_eq!(db.glob_count(), 2);
+    assert!(db.query_filename("a.hkm").is_unknown());
+    assert_eq!(db.query_filename("a.muche").best(), Some("text/x-hackem"));
+    assert_eq!(db.query_filename("a.fb").best(), Some("text/x-foobie"));
+}
+
+#[test]
+fn test_magic_deleteall() {
+    use crate::record::{MagicMatch, MagicRule};
+
+    let magic = |value: &[u8]| MagicRule {
+        priority: 50,
+        matches: vec![MagicMatch {
+            offset: 0,
+            range_length: 1,
+            word_size: 1,
+            value: value.to_vec(),
+            mask: None,
+            children: vec![],
+        }],
+    };
+    let mut db = MimeDB::new();
+    db.add_records(vec![MimeTypeRecord {
+        name: "application/x-hackem".into(),
+        magic: vec![magic(b"HKM")],
+        ..Default::default()
+    }]);
+    db.add_records(vec![MimeTypeRecord {
+        name: "application/x-hackem".into(),
+        magic: vec![magic(b"MUCHE")],
+        magic_deleteall: true,
+        ..Default::default()
+    }]);
+    assert_eq!(db.magic_count(), 1);
+    assert!(db.query_magic(b"HKM1").is_unknown());
+    assert_eq!(
+        db.query_magic(b"MUCHE1").best(),
+        Some("application/x-hackem")
+    );
+}
@@ -0,0 +1,409 @@
+//! Rust source generation for precompiled databases.
+//!
+//! The generated source defines a [StaticIndex](super::StaticIndex) in terms of
+//! the types in [crate::index], so a build script can compile a database into
+//! a crate and load it with [MimeDB::from_static].
+use std::borrow::Cow;
+use std::hash::Hash;
+use std::io::{self, Write};
+
+use crate::{
+    fnmatch::{FileMatcher, MatchElement, MatchRule},
+    magic::{MagicMatcher, Matchlet},
+    record::TreeFileType,
+    strcache::CachedString,
+    table::Table,
+};
+
+use super::{
+    GlobIndex, GlobRule, MagicRule, MimeDB, SuffixNode, TreeMagicRule, TreeMatcher, TypeInfo,
+};
+
+impl MimeDB {
+    /// Write Rust source defining this database as a static index.
+    ///
+    /// The source defines `pub static {name}: StaticIndex`, and is intended
+    /// to be included (with [include!]) in its own module.
+    pub fn write_static<W: Write>(&self, name: &str, out: &mut W) -> io::Result<()> {
+        writeln!(out, "// generated by shared-mime, do not edit")?;
+        writeln!(out, "#[allow(unused_imports)]")?;
+        writeln!(out, "use ::std::borrow::Cow::Borrowed as B;")?;
+        writeln!(out, "#[allow(unused_imports)]")?;
+        writeln!(out, "use ::shared_mime::index::*;")?;
+        writeln!(out)?;
+        writeln!(out, "pub static {}: StaticIndex = StaticIndex {{", name)?;
+        writeln!(out, "sequence: {},", self.sequence)?;
+        write!(out, "types: ")?;
+        emit_slice(out, self.type_info.sorted().iter())?;
+        write!(out, ",\nglobs: ")?;
+        emit_slice(out, self.globs.iter())?;
+        write!(out, ",\nglob_index: ")?;
+        self.glob_index.emit(out)?;
+        write!(out, ",\nmagic: ")?;
+        emit_slice(out, self.magic.iter())?;
+        write!(out, ",\ntreemagic: ")?;
+        emit_slice(out, self.treemagic.iter())?;
+        write!(out, ",\nmagic_extent: {}", self.magic_extent)?;
+        write!(out, ",\naliases: ")?;
+        emit_slice(out, self.aliases.sorted().iter())?;
+        write!(out, ",\nroot_xml: ")?;
+        emit_slice(out, self.root_xml.sorted().iter())?;
+        write!(out, ",\norigins: ")?;
+        emit_slice(out, self.origins.sorted().iter())?;
+        writeln!(out, ",\n}};")?;
+        Ok(())
+    }
+}
+
+/// Values that can be written as Rust expressions.
+trait Source {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Write a slice literal (`&[...]`).
+fn emit_slice<'a, T, I>(out: &mut dyn Write, items: I) -> io::Result<()>
+where
+    T: Source + 'a,
+    I: IntoIterator<Item = &'a T>,
+{
+    write!(out, "&[")?;
+    for item in items {
+        item.emit(out)?;
+        writeln!(out, ",")?;
+    }
+    write!(out, "]")
+}
+
+/// Write a byte value that is stored in host byte order, selecting the byte
+/// order of the target at compile time.
+fn emit_host_bytes(out: &mut dyn Write, host: &[u8], word_size: usize) -> io::Result<()> {
+    let swapped: Vec<u8> = host
+        .chunks(word_size)
+        .flat_map(|c| c.iter().rev().copied())
+        .collect();
+    let (le, be) = if cfg!(target_endian = "little") {
+        (host, &swapped[..])
+    } else {
+        (&swapped[..], host)
+    };
+    if le == be {
+        return write!(out, "B(&{:?})", le);
+    }
+    write!(
+        out,
+        "if cfg!(target_endian = \"little\") {{ B(&{:?}) }}",
+        le
+    )?;
+    write!(out, " else {{ B(&{:?}) }}", be)
+}
+
+/// Write a struct literal with the given fields.
+macro_rules! emit_struct {
+    ($out:expr, $name:literal, $self:ident { $($field:ident),* $(,)? }) => {{
+        write!($out, concat!($name, " {{ "))?;
+        $(
+            write!($out, concat!(stringify!($field), ": "))?;
+            $self.$field.emit($out)?;
+            write!($out, ", ")?;
+        )*
+        write!($out, "}}")
+    }};
+}
+
+impl<T: Source + ?Sized> Source for &T {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        (**self).emit(out)
+    }
+}
+
+impl Source for bool {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "{}", self)
+    }
+}
+
+impl Source for i32 {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "{}", self)
+    }
+}
+
+impl Source for u32 {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "{}", self)
+    }
+}
+
+impl Source for usize {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "{}", self)
+    }
+}
+
+impl Source for u8 {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "{}", self)
+    }
+}
+
+impl Source for Cow<'static, str> {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "B({:?})", self.as_ref())
+    }
+}
+
+impl Source for CachedString {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "CachedString::from_static({:?})", self.as_str())
+    }
+}
+
+impl<T: Source + Clone> Source for Cow<'static, [T]> {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "B(")?;
+        emit_slice(out, self.iter())?;
+        write!(out, ")")
+    }
+}
+
+impl<T: Source> Source for Option<T> {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        match self {
+            Some(v) => {
+                write!(out, "Some(")?;
+                v.emit(out)?;
+                write!(out, ")")
+            }
+            None => write!(out, "None"),
+        }
+    }
+}
+
+impl<A: Source, B: Source> Source for (A, B) {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "(")?;
+        self.0.emit(out)?;
+        write!(out, ", ")?;
+        self.1.emit(out)?;
+        write!(out, ")")
+    }
+}
+
+impl<K, V> Source for Table<K, V>
+where
+    K: Source + Hash + Eq + Ord + Clone,
+    V: Source + Clone,
+{
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "Table::Static(")?;
+        emit_slice(out, self.sorted().iter())?;
+        write!(out, ")")
+    }
+}
+
+impl Source for TypeInfo {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        emit_struct!(
+            out,
+            "TypeInfo",
+            self {
+                description,
+                localized_descriptions,
+                icon,
+                generic_icon,
+                acronym,
+                expanded_acronym,
+                aliases,
+                parents,
+            }
+        )
+    }
+}
+
+impl Source for GlobRule {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        emit_struct!(
+            out,
+            "GlobRule",
+            self {
+                pattern,
+                matcher,
+                sequence,
+                weight,
+                mimetype
+            }
+        )
+    }
+}
+
+impl Source for FileMatcher {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        emit_struct!(
+            out,
+            "FileMatcher",
+            self {
+                rule,
+                case_sensitive
+            }
+        )
+    }
+}
+
+impl Source for MatchRule {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        let (name, body): (&str, &dyn Source) = match self {
+            MatchRule::Literal(lit) => ("Literal", lit),
+            MatchRule::Suffix(sfx) => ("Suffix", sfx),
+            MatchRule::Pattern(pat) => ("Pattern", pat),
+        };
+        write!(out, "MatchRule::{}(", name)?;
+        body.emit(out)?;
+        write!(out, ")")
+    }
+}
+
+impl Source for MatchElement {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        match self {
+            MatchElement::Star => write!(out, "MatchElement::Star"),
+            MatchElement::Wildcard => write!(out, "MatchElement::Wildcard"),
+            MatchElement::Set { negated, ranges } => {
+                write!(out, "MatchElement::Set {{ negated: {}, ranges: ", negated)?;
+                ranges.emit(out)?;
+                write!(out, " }}")
+            }
+            MatchElement::Literal(lit) => {
+                write!(out, "MatchElement::Literal(")?;
+                lit.emit(out)?;
+                write!(out, ")")
+            }
+        }
+    }
+}
+
+impl Source for GlobIndex {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        emit_struct!(
+            out,
+            "GlobIndex",
+            self {
+                literals,
+                suffixes,
+                patterns,
+                by_type
+            }
+        )
+    }
+}
+
+impl Source for SuffixNode {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        emit_struct!(
+            out,
+            "SuffixNode",
+            self {
+                byte,
+                globs,
+                children
+            }
+        )
+    }
+}
+
+impl Source for MagicRule {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        emit_struct!(
+            out,
+            "MagicRule",
+            self {
+                matcher,
+                sequence,
+                priority,
+                mimetype
+            }
+        )
+    }
+}
+
+impl Source for MagicMatcher {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        emit_struct!(out, "MagicMatcher", self { matches })
+    }
+}
+
+impl Source for Matchlet {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        if self.word_size <= 1 {
+            return emit_struct!(
+                out,
+                "Matchlet",
+                self {
+                    offset,
+                    range_length,
+                    word_size,
+                    value,
+                    mask,
+                    children
+                }
+            );
+        }
+        // host-endian values were swapped for the build host, and need to be
+        // emitted for the target
+        write!(out, "Matchlet {{ ")?;
+        write!(out, "offset: {}, ", self.offset)?;
+        write!(out, "range_length: {}, ", self.range_length)?;
+        write!(out, "word_size: {}, ", self.word_size)?;
+        write!(out, "value: ")?;
+        emit_host_bytes(out, &self.value, self.word_size)?;
+        write!(out, ", mask: ")?;
+        if let Some(mask) = &self.mask {
+            write!(out, "Some(")?;
+            emit_host_bytes(out, mask, self.word_size)?;
+            write!(out, ")")?;
+        } else {
+            write!(out, "None")?;
+        }
+        write!(out, ", children: ")?;
+        self.children.emit(out)?;
+        write!(out, " }}")
+    }
+}
+
+impl Source for TreeMagicRule {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        emit_struct!(
+            out,
+            "TreeMagicRule",
+            self {
+                matches,
+                sequence,
+                priority,
+                mimetype
+            }
+        )
+    }
+}
+
+impl Source for TreeMatcher {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        emit_struct!(
+            out,
+            "TreeMatcher",
+            self {
+                path,
+                file_type,
+                match_case,
+                executable,
+                non_empty,
+                mimetype,
+                children
+            }
+        )
+    }
+}
+
+impl Source for TreeFileType {
+    fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "TreeFileType::{:?}", self)
+    }
+}
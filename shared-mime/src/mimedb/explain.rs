@@ -0,0 +1,222 @@
+//! Step-by-step explanations of queries.
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{query::FileQuery, OwnedAnswer, QueryError};
+
+use super::MimeDB;
+
+/// A record of how [MimeDB::query] arrived at its answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Explanation {
+    /// The steps of the query, in order.
+    pub steps: Vec<ExplainStep>,
+    /// The final answer.
+    pub answer: OwnedAnswer,
+}
+
+/// A single step of a query.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "kebab-case")]
+pub enum ExplainStep {
+    /// File metadata was checked; special files have an `inode/` type.
+    Metadata {
+        mimetype: Option<String>,
+        size: Option<u64>,
+    },
+    /// A glob matched the file name.
+    ///
+    /// Literal globs are checked first; if one matches, the other globs are
+    /// not checked, so their matches are not reported.
+    GlobMatch {
+        pattern: String,
+        mimetype: String,
+        sequence: i32,
+        weight: i32,
+    },
+    /// The best-ranked glob matches were kept, and the rest discarded.
+    ///
+    /// This ends the glob search; if `literal` is true, it stopped after the
+    /// literal globs.
+    GlobSelected {
+        literal: bool,
+        sequence: i32,
+        weight: i32,
+        pattern_len: usize,
+        mimetypes: Vec<String>,
+    },
+    /// Multiple candidates were sorted, supertypes first, and collapsed into
+    /// their supertype if possible.
+    Coalesce {
+        mimetypes: Vec<String>,
+        collapsed: bool,
+        ambiguous: bool,
+    },
+    /// Magic rules matched the content.
+    Magic {
+        priority: u32,
+        mimetypes: Vec<String>,
+    },
+    /// Magic results were combined with the glob results.
+    ResolveMagic { mimetypes: Vec<String> },
+    /// An XML root element was checked.
+    XmlRoot { mimetype: Option<String> },
+    /// No rule identified the type, so a fallback type was used.
+    Fallback { mimetype: String },
+}
+
+/// Collects explanation steps when explaining, and does nothing otherwise.
+pub(super) struct Trace(Option<Vec<ExplainStep>>);
+
+impl Trace {
+    pub(super) fn off() -> Trace {
+        Trace(None)
+    }
+
+    pub(super) fn on() -> Trace {
+        Trace(Some(Vec::new()))
+    }
+
+    /// Record a step, only building it if the trace is on.
+    pub(super) fn note<F: FnOnce() -> ExplainStep>(&mut self, step: F) {
+        if let Some(steps) = &mut self.0 {
+            steps.push(step());
+        }
+    }
+}
+
+impl MimeDB {
+    /// Query the MIME database, recording the steps taken to reach the answer.
+    pub fn explain(&self, query: &FileQuery<'_>) -> Explanation {
+        let mut trace = Trace::on();
+        let answer = self.query_traced(query, &mut trace).into_owned();
+        Explanation {
+            steps: trace.0.unwrap_or_default(),
+            answer,
+        }
+    }
+
+    /// Explain the query for a file path, reading its content as
+    /// [MimeDB::query_path] does.
+    pub fn explain_path<P: AsRef<Path>>(&self, path: P) -> Result<Explanation, QueryError> {
+        let query = FileQuery::for_path_in(path.as_ref(), self)?;
+        Ok(self.explain(&query))
+    }
+}
+
+impl fmt::Display for ExplainStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExplainStep::Metadata {
+                mimetype: Some(mt), ..
+            } => write!(f, "metadata: special file {}", mt),
+            ExplainStep::Metadata { size, .. } => match size {
+                Some(size) => write!(f, "metadata: regular file, {} bytes", size),
+                None => write!(f, "metadata: regular file"),
+            },
+            ExplainStep::GlobMatch {
+                pattern,
+                mimetype,
+                sequence,
+                weight,
+            } => write!(
+                f,
+                "glob {} matches {} (sequence {}, weight {})",
+                pattern, mimetype, sequence, weight
+            ),
+            ExplainStep::GlobSelected {
+                literal,
+                sequence,
+                weight,
+                pattern_len,
+                mimetypes,
+            } => {
+                write!(
+                    f,
+                    "best globs (literal {}, sequence {}, weight {}, length {}): {}",
+                    literal,
+                    sequence,
+                    weight,
+                    pattern_len,
+                    mimetypes.join(", ")
+                )?;
+                if *literal {
+                    write!(f, " (other globs not checked)")
+                } else {
+                    Ok(())
+                }
+            }
+            ExplainStep::Coalesce {
+                mimetypes,
+                collapsed,
+                ambiguous,
+            } => {
+                write!(f, "candidates: {}", mimetypes.join(", "))?;
+                if *ambiguous {
+                    write!(f, " (ambiguous)")
+                } else if *collapsed {
+                    write!(f, " (collapsed to {})", mimetypes[0])
+                } else {
+                    Ok(())
+                }
+            }
+            ExplainStep::Magic {
+                priority,
+                mimetypes,
+            } => write!(f, "magic (priority {}): {}", priority, mimetypes.join(", ")),
+            ExplainStep::ResolveMagic { mimetypes } => {
+                write!(f, "combined globs and magic: {}", mimetypes.join(", "))
+            }
+            ExplainStep::XmlRoot { mimetype: Some(mt) } => write!(f, "XML root element: {}", mt),
+            ExplainStep::XmlRoot { mimetype: None } => write!(f, "XML root element: no match"),
+            ExplainStep::Fallback { mimetype } => write!(f, "fallback: {}", mimetype),
+        }
+    }
+}
+
+#[test]
+fn test_explain_globs() {
+    use crate::record::glob_record;
+
+    let mut db = MimeDB::new();
+    db.add_records(vec![
+        glob_record("application/gzip", &[("*.gz", 50)]),
+        glob_record("application/x-compressed-tar", &[("*.tar.gz", 50)]),
+    ]);
+    let query = FileQuery::builder()
+        .filename(std::ffi::OsStr::new("a.tar.gz"))
+        .build();
+    let exp = db.explain(&query);
+    assert_eq!(exp.answer.best(), Some("application/x-compressed-tar"));
+    let matched: Vec<&str> = exp
+        .steps
+        .iter()
+        .filter_map(|s| match s {
+            ExplainStep::GlobMatch { pattern, .. } => Some(pattern.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(matched, vec!["*.gz", "*.tar.gz"]);
+    assert!(exp.steps.contains(&ExplainStep::GlobSelected {
+        literal: false,
+        sequence: 1,
+        weight: 50,
+        pattern_len: 8,
+        mimetypes: vec!["application/x-compressed-tar".into()],
+    }));
+}
+
+#[test]
+fn test_explain_fallback() {
+    let db = MimeDB::new();
+    let query = FileQuery::builder().content(b"\x00\x01\x02").build();
+    let exp = db.explain(&query);
+    assert_eq!(
+        exp.steps.last(),
+        Some(&ExplainStep::Fallback {
+            mimetype: "application/octet-stream".into()
+        })
+    );
+}
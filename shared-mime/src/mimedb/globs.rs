@@ -0,0 +1,302 @@
+//! Index of glob rules for fast file name lookup.
+//!
+//! Like `mime.cache`, this puts literal globs in a table and simple suffix
+//! globs (`*.ext`) in a trie of reversed bytes, so only the remaining patterns
+//! need to be scanned.  The index refers to rules by their position in the
+//! sorted glob list, so lookups can preserve the list's search order.
+//!
+//! The index also lists each type's globs, for looking up file name patterns
+//! and extensions from a type.
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::{
+    fnmatch::{fold_case, MatchRule},
+    strcache::CachedString,
+    table::Table,
+};
+
+use super::{GlobRule, MimeDB};
+
+/// Index of glob rules by position.
+#[derive(Debug, Clone, Default)]
+pub struct GlobIndex {
+    /// Literal globs, keyed by case-folded file name.
+    pub literals: Table<Cow<'static, [u8]>, Cow<'static, [u32]>>,
+    /// Suffix globs, in a trie of case-folded reversed suffixes.
+    pub suffixes: SuffixNode,
+    /// All other globs.
+    pub patterns: Cow<'static, [u32]>,
+    /// Globs for each type.
+    pub by_type: Table<CachedString, Cow<'static, [u32]>>,
+}
+
+/// A node in the suffix trie.
+#[derive(Debug, Clone, Default)]
+pub struct SuffixNode {
+    pub byte: u8,
+    /// Globs whose suffix ends at this node.
+    pub globs: Cow<'static, [u32]>,
+    /// Child nodes, sorted by byte.
+    pub children: Cow<'static, [SuffixNode]>,
+}
+
+impl GlobIndex {
+    /// Build the index for a sorted list of globs.
+    pub(super) fn build(globs: &[GlobRule]) -> GlobIndex {
+        let mut literals: HashMap<Cow<'static, [u8]>, Vec<u32>> = HashMap::new();
+        let mut suffixes = SuffixNode::default();
+        let mut patterns = Vec::new();
+        let mut by_type: HashMap<CachedString, Vec<u32>> = HashMap::new();
+        for (i, glob) in globs.iter().enumerate() {
+            let i = i as u32;
+            by_type.entry(glob.mimetype.clone()).or_default().push(i);
+            match &glob.matcher.rule {
+                MatchRule::Literal(lit) => {
+                    literals.entry(fold_case(lit).into()).or_default().push(i)
+                }
+                MatchRule::Suffix(sfx) => suffixes.insert(&fold_case(sfx), i),
+                MatchRule::Pattern(_) => patterns.push(i),
+            }
+        }
+        GlobIndex {
+            literals: Table::Owned(literals.into_iter().map(|(k, v)| (k, v.into())).collect()),
+            suffixes,
+            patterns: patterns.into(),
+            by_type: Table::Owned(by_type.into_iter().map(|(k, v)| (k, v.into())).collect()),
+        }
+    }
+
+    /// Get the positions of the globs that may match a file name, which must
+    /// already be folded with [fold_case].
+    ///
+    /// Candidates still need to be checked against the file name, as the
+    /// index ignores case sensitivity.
+    pub(super) fn candidates<'a>(&'a self, lname: &[u8]) -> Candidates<'a> {
+        let literals = match self.literals.get(lname) {
+            Some(globs) => globs.as_ref(),
+            None => &[],
+        };
+        let mut suffixes = Vec::new();
+        let mut node = &self.suffixes;
+        for b in lname.iter().rev() {
+            match node.children.binary_search_by_key(b, |n| n.byte) {
+                Ok(i) => node = &node.children[i],
+                Err(_) => break,
+            }
+            suffixes.extend_from_slice(&node.globs);
+        }
+        suffixes.sort_unstable();
+        Candidates {
+            literals,
+            suffixes,
+            patterns: &self.patterns,
+        }
+    }
+}
+
+/// The globs that may match a file name, from [GlobIndex::candidates].
+///
+/// Literal globs outrank all others, so they are kept apart; the rest are
+/// merged in glob list order as they are iterated.
+pub(super) struct Candidates<'a> {
+    literals: &'a [u32],
+    suffixes: Vec<u32>,
+    patterns: &'a [u32],
+}
+
+impl Candidates<'_> {
+    /// Get the literal globs, in order.
+    pub(super) fn literals(&self) -> impl Iterator<Item = usize> + '_ {
+        self.literals.iter().map(|i| *i as usize)
+    }
+
+    /// Get the suffix globs and other patterns, in order.
+    pub(super) fn others(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut suffixes = self.suffixes.iter().peekable();
+        let mut patterns = self.patterns.iter().peekable();
+        std::iter::from_fn(move || {
+            let next = match (suffixes.peek(), patterns.peek()) {
+                (Some(s), Some(p)) if s < p => suffixes.next(),
+                (Some(_), None) => suffixes.next(),
+                _ => patterns.next(),
+            };
+            next.map(|i| *i as usize)
+        })
+    }
+}
+
+impl MimeDB {
+    /// Get the file name patterns for a type.
+    ///
+    /// Patterns are ordered by weight, and then with patterns from later
+    /// packages first.  If `inherit` is true, the patterns of the type's
+    /// supertypes follow its own, closest supertypes first.
+    pub fn globs_for_type(&self, typ: &str, inherit: bool) -> Vec<&str> {
+        let typ = self.canonical(typ);
+        let types = if inherit {
+            self.supertypes(typ)
+        } else {
+            vec![self.names.lookup(typ)]
+        };
+        let mut patterns = Vec::new();
+        for t in types {
+            let Some(globs) = self.glob_index.by_type.get(t.as_str()) else {
+                continue;
+            };
+            let mut globs: Vec<&GlobRule> =
+                globs.iter().map(|i| &self.globs[*i as usize]).collect();
+            // the glob list is in sequence order, so this keeps later packages first
+            globs.sort_by_key(|g| Reverse(g.weight));
+            for g in globs {
+                if !patterns.contains(&g.pattern.as_ref()) {
+                    patterns.push(g.pattern.as_ref());
+                }
+            }
+        }
+        patterns
+    }
+
+    /// Get the file name extensions (without the leading `.`) for a type.
+    ///
+    /// These are taken from the type's simple suffix patterns (such as
+    /// `*.tar.gz`), in the order of [MimeDB::globs_for_type].
+    pub fn extensions_for_type(&self, typ: &str, inherit: bool) -> Vec<&str> {
+        self.globs_for_type(typ, inherit)
+            .into_iter()
+            .filter_map(pattern_extension)
+            .collect()
+    }
+
+    /// Get the preferred file name extension for a type, if it has one.
+    ///
+    /// Extensions are not inherited from supertypes.
+    pub fn primary_extension(&self, typ: &str) -> Option<&str> {
+        self.extensions_for_type(typ, false).into_iter().next()
+    }
+}
+
+/// Get the extension matched by a simple suffix pattern (`*.ext`).
+fn pattern_extension(pattern: &str) -> Option<&str> {
+    let ext = pattern.strip_prefix("*.")?;
+    if ext.is_empty() || ext.contains(['*', '?', '[', '\\']) {
+        None
+    } else {
+        Some(ext)
+    }
+}
+
+impl SuffixNode {
+    fn insert(&mut self, suffix: &[u8], glob: u32) {
+        let Some((last, rest)) = suffix.split_last() else {
+            self.globs.to_mut().push(glob);
+            return;
+        };
+        let children = self.children.to_mut();
+        let pos = match children.binary_search_by_key(last, |n| n.byte) {
+            Ok(pos) => pos,
+            Err(pos) => {
+                children.insert(
+                    pos,
+                    SuffixNode {
+                        byte: *last,
+                        ..Default::default()
+                    },
+                );
+                pos
+            }
+        };
+        children[pos].insert(rest, glob);
+    }
+}
+
+#[cfg(test)]
+fn test_globs(patterns: &[&str]) -> Vec<GlobRule> {
+    use crate::{fnmatch::FileMatcher, strcache::CachedString};
+
+    patterns
+        .iter()
+        .map(|p| GlobRule {
+            pattern: p.to_string().into(),
+            matcher: FileMatcher::new(p),
+            sequence: 1,
+            weight: 50,
+            mimetype: CachedString::from_static("text/x-hackem"),
+        })
+        .collect()
+}
+
+#[test]
+fn test_index_candidates() {
+    let globs = test_globs(&["*.tar.gz", "Makefile", "*.gz", "*[0-9].txt", "*.GZ"]);
+    let index = GlobIndex::build(&globs);
+    let candidates = |name: &[u8]| {
+        let found = index.candidates(name);
+        (
+            found.literals().collect::<Vec<_>>(),
+            found.others().collect::<Vec<_>>(),
+        )
+    };
+    assert_eq!(candidates(b"foo.tar.gz"), (vec![], vec![0, 2, 3, 4]));
+    assert_eq!(candidates(b"makefile"), (vec![1], vec![3]));
+    assert_eq!(candidates(b"foo.txt"), (vec![], vec![3]));
+}
+
+#[test]
+fn test_index_matches_scan() {
+    let globs = test_globs(&["*.tar.gz", "Makefile", "*.gz", "*[0-9].txt", "*.GZ", "*"]);
+    let index = GlobIndex::build(&globs);
+    for name in [
+        "a.tar.gz", "A.TAR.GZ", "Makefile", "x9.txt", "x.txt", "gz", "",
+    ] {
+        let found = index.candidates(&fold_case(name.as_bytes()));
+        let mut indexed: Vec<usize> = found
+            .literals()
+            .chain(found.others())
+            .filter(|i| globs[*i].matcher.matches(name.as_bytes()))
+            .collect();
+        indexed.sort_unstable();
+        let scanned: Vec<usize> = (0..globs.len())
+            .filter(|i| globs[*i].matcher.matches(name.as_bytes()))
+            .collect();
+        assert_eq!(indexed, scanned, "{}", name);
+    }
+}
+
+#[test]
+fn test_pattern_extension() {
+    assert_eq!(pattern_extension("*.tar.gz"), Some("tar.gz"));
+    assert_eq!(pattern_extension("*.[ch]"), None);
+    assert_eq!(pattern_extension("Makefile"), None);
+    assert_eq!(pattern_extension("*."), None);
+}
+
+#[test]
+fn test_globs_for_type() {
+    use crate::record::glob_record;
+
+    let mut db = MimeDB::new();
+    let mut child = glob_record("text/x-hackem-muche", &[("*.hkm", 50), ("*.muche", 60)]);
+    child.superclasses.push("text/x-hackem".into());
+    child.aliases.push("text/x-muche".into());
+    db.add_records(vec![
+        glob_record("text/x-hackem", &[("*.hackem", 50), ("HACKEM", 50)]),
+        child,
+    ]);
+    db.add_records(vec![glob_record("text/x-hackem-muche", &[("*.hm", 50)])]);
+    assert_eq!(
+        db.globs_for_type("text/x-hackem-muche", false),
+        vec!["*.muche", "*.hm", "*.hkm"]
+    );
+    assert_eq!(
+        db.globs_for_type("text/x-muche", true),
+        vec!["*.muche", "*.hm", "*.hkm", "*.hackem", "HACKEM"]
+    );
+    assert_eq!(
+        db.extensions_for_type("text/x-hackem", false),
+        vec!["hackem"]
+    );
+    assert_eq!(db.primary_extension("text/x-muche"), Some("muche"));
+    assert_eq!(db.primary_extension("text/x-foobie"), None);
+}
@@ -1,37 +1,147 @@
 //! The [MimeDB] type for file type lookup.
-use std::{cmp::Ordering, collections::HashMap};
+use std::{borrow::Cow, cmp::Ordering};
 
 mod build;
+mod codegen;
+mod explain;
+mod globs;
 mod query;
+mod tree;
+
+pub use explain::{ExplainStep, Explanation};
+pub use globs::{GlobIndex, SuffixNode};
+pub use query::MAGIC_HIGH_PRIORITY;
+pub use tree::TreeMatcher;
 
 use crate::{
     fnmatch::FileMatcher,
+    locale::{current_languages, locale_fallbacks},
+    magic::MagicMatcher,
     search_queue::SearchQueue,
     strcache::{CachedString, StringCache},
+    table::Table,
+    text::TextRules,
 };
 
 /// Hold MIME data and facilitate  file type guessing.
-#[derive(Default)]
+///
+/// Once built, a database is read-only and can be shared between threads
+/// (e.g. in an [Arc](std::sync::Arc)).
 pub struct MimeDB {
     names: StringCache,
-    type_info: HashMap<CachedString, TypeInfo>,
+    type_info: Table<CachedString, TypeInfo>,
     sequence: i32,
-    globs: Vec<GlobRule>,
+    globs: Cow<'static, [GlobRule]>,
+    glob_index: GlobIndex,
+    magic: Cow<'static, [MagicRule]>,
+    treemagic: Cow<'static, [TreeMagicRule]>,
+    /// The number of leading bytes needed to evaluate all magic rules.
+    magic_extent: usize,
+    /// Canonical types, keyed by alias.
+    aliases: Table<CachedString, CachedString>,
+    /// XML root elements (with their sequence), keyed by `"{namespace} {local name}"`.
+    root_xml: Table<Cow<'static, str>, (CachedString, i32)>,
+    /// Names of the sources of records, keyed by sequence.
+    origins: Table<i32, Cow<'static, str>>,
+    text_rules: TextRules,
+    collapse_supertypes: bool,
 }
 
+/// Compiled information about a single type.
 #[derive(Debug, Clone, Default)]
-struct TypeInfo {
-    description: Option<String>,
-    aliases: Vec<CachedString>,
-    parents: Vec<CachedString>,
+pub struct TypeInfo {
+    pub description: Option<Cow<'static, str>>,
+    /// Translated descriptions, sorted by language.
+    pub localized_descriptions: Cow<'static, [(Cow<'static, str>, Cow<'static, str>)]>,
+    pub icon: Option<Cow<'static, str>>,
+    pub generic_icon: Option<Cow<'static, str>>,
+    pub acronym: Option<Cow<'static, str>>,
+    pub expanded_acronym: Option<Cow<'static, str>>,
+    pub aliases: Cow<'static, [CachedString]>,
+    pub parents: Cow<'static, [CachedString]>,
 }
 
+/// A compiled glob rule.
 #[derive(Debug, Clone)]
-struct GlobRule {
-    matcher: FileMatcher,
-    sequence: i32,
-    weight: i32,
-    mimetype: String,
+pub struct GlobRule {
+    /// The pattern, as written in the package.
+    pub pattern: Cow<'static, str>,
+    pub matcher: FileMatcher,
+    pub sequence: i32,
+    pub weight: i32,
+    pub mimetype: CachedString,
+}
+
+/// A compiled magic rule.
+#[derive(Debug, Clone)]
+pub struct MagicRule {
+    pub matcher: MagicMatcher,
+    pub sequence: i32,
+    pub priority: u32,
+    pub mimetype: CachedString,
+}
+
+/// A compiled tree magic rule.
+#[derive(Debug, Clone)]
+pub struct TreeMagicRule {
+    pub matches: Cow<'static, [TreeMatcher]>,
+    pub sequence: i32,
+    pub priority: u32,
+    pub mimetype: CachedString,
+}
+
+/// A fully-built MIME database in static data, as written by
+/// [MimeDB::write_static].
+#[derive(Debug)]
+pub struct StaticIndex {
+    pub sequence: i32,
+    /// Type information, sorted by type name.
+    pub types: &'static [(CachedString, TypeInfo)],
+    /// Glob rules, in search order.
+    pub globs: &'static [GlobRule],
+    /// Index of the glob rules.
+    pub glob_index: GlobIndex,
+    /// Magic rules, in search order.
+    pub magic: &'static [MagicRule],
+    /// Tree magic rules, in search order.
+    pub treemagic: &'static [TreeMagicRule],
+    /// The number of leading bytes needed to evaluate all magic rules.
+    pub magic_extent: usize,
+    /// Canonical types, sorted by alias.
+    pub aliases: &'static [(CachedString, CachedString)],
+    /// XML root elements, sorted by key.
+    pub root_xml: &'static [(Cow<'static, str>, (CachedString, i32))],
+    /// Names of the sources of records, sorted by sequence.
+    pub origins: &'static [(i32, Cow<'static, str>)],
+}
+
+impl Default for MimeDB {
+    fn default() -> Self {
+        MimeDB {
+            names: StringCache::default(),
+            type_info: Table::default(),
+            sequence: 0,
+            globs: Cow::default(),
+            glob_index: GlobIndex::default(),
+            magic: Cow::default(),
+            treemagic: Cow::default(),
+            magic_extent: 0,
+            aliases: Table::default(),
+            root_xml: Table::default(),
+            origins: Table::default(),
+            text_rules: TextRules::default(),
+            collapse_supertypes: true,
+        }
+    }
+}
+
+impl TypeInfo {
+    fn localized_description(&self, lang: &str) -> Option<&str> {
+        self.localized_descriptions
+            .binary_search_by(|(l, _)| l.as_ref().cmp(lang))
+            .ok()
+            .map(|i| self.localized_descriptions[i].1.as_ref())
+    }
 }
 
 impl MimeDB {
@@ -40,6 +150,28 @@ impl MimeDB {
         MimeDB::default()
     }
 
+    /// Construct a database backed by a precompiled static index.
+    ///
+    /// This does not copy or allocate; the index is only copied if more
+    /// records are added to the database.
+    pub fn from_static(index: &'static StaticIndex) -> MimeDB {
+        MimeDB {
+            names: StringCache::default(),
+            type_info: Table::Static(index.types),
+            sequence: index.sequence,
+            globs: Cow::Borrowed(index.globs),
+            glob_index: index.glob_index.clone(),
+            magic: Cow::Borrowed(index.magic),
+            treemagic: Cow::Borrowed(index.treemagic),
+            magic_extent: index.magic_extent,
+            aliases: Table::Static(index.aliases),
+            root_xml: Table::Static(index.root_xml),
+            origins: Table::Static(index.origins),
+            text_rules: TextRules::default(),
+            collapse_supertypes: true,
+        }
+    }
+
     /// Get the number of known types.
     pub fn type_count(&self) -> usize {
         self.type_info.len()
@@ -50,21 +182,83 @@ impl MimeDB {
         self.globs.len()
     }
 
+    /// Get the number of magic rules.
+    pub fn magic_count(&self) -> usize {
+        self.magic.len()
+    }
+
+    /// Get the number of tree magic rules.
+    pub fn treemagic_count(&self) -> usize {
+        self.treemagic.len()
+    }
+
+    /// Get the number of leading bytes of a file needed to evaluate all magic rules.
+    pub fn magic_extent(&self) -> usize {
+        self.magic_extent
+    }
+
+    /// Get the rules used to detect text files.
+    pub fn text_rules(&self) -> &TextRules {
+        &self.text_rules
+    }
+
+    /// Set the rules used to detect text files.
+    pub fn set_text_rules(&mut self, rules: TextRules) {
+        self.text_rules = rules;
+    }
+
+    /// Query whether ambiguous matches are collapsed to a common supertype.
+    pub fn collapse_supertypes(&self) -> bool {
+        self.collapse_supertypes
+    }
+
+    /// Set whether ambiguous matches are collapsed to a common supertype.
+    ///
+    /// This is an extension to the specification (enabled by default): if a
+    /// lookup matches several types, and one of them is a supertype of all the
+    /// others, the lookup resolves to that type instead of being ambiguous.
+    pub fn set_collapse_supertypes(&mut self, collapse: bool) {
+        self.collapse_supertypes = collapse;
+    }
+
+    /// Get the name of the source of the records added with a sequence number.
+    fn origin(&self, sequence: i32) -> Option<&str> {
+        self.origins.get(&sequence).map(|o| o.as_ref())
+    }
+
+    /// Get the canonical name of a type, resolving aliases.
+    ///
+    /// Types that are not aliases are returned unchanged.
+    pub fn canonical<'a>(&'a self, typ: &'a str) -> &'a str {
+        self.aliases.get(typ).map_or(typ, |c| c.as_str())
+    }
+
+    fn canonical_name(&self, typ: &CachedString) -> CachedString {
+        self.aliases.get(typ).unwrap_or(typ).clone()
+    }
+
+    /// Query whether a type name is an alias for another type.
+    pub fn is_alias(&self, typ: &str) -> bool {
+        self.aliases.get(typ).is_some()
+    }
+
     /// Query whether one type is a subtype of another.
     pub fn is_subtype(&self, typ: &str, sup: &str) -> bool {
+        let typ = self.canonical(typ);
+        let sup = self.canonical(sup);
         // everything is an octet stream
         if sup == "application/octet-stream" && !typ.starts_with("inode/") {
             return true;
         }
         let mut queue: SearchQueue<CachedString> = SearchQueue::new();
-        queue.maybe_add(self.names.cache(typ));
+        queue.maybe_add(self.names.lookup(typ));
         while let Some(q) = queue.get() {
             if q == sup || sup == "text/plain" && q.starts_with("text/") {
                 return true;
             }
             if let Some(info) = self.type_info.get(&q) {
                 for pt in info.parents.iter() {
-                    queue.maybe_add(pt.clone());
+                    queue.maybe_add(self.canonical_name(pt));
                 }
             }
         }
@@ -73,14 +267,109 @@ impl MimeDB {
 
     /// Get the description of a string.
     pub fn description(&self, typ: &str) -> Option<&str> {
+        let typ = self.canonical(typ);
+
+        self.type_info
+            .get(typ)
+            .and_then(|ti| ti.description.as_deref())
+    }
+
+    /// Get the description of a type in the first available of a list of locales.
+    ///
+    /// Each locale falls back through less-specific names (`pt_BR` → `pt`), and
+    /// if no translation is found, the untranslated description is returned.
+    pub fn description_for_locale<S: AsRef<str>>(&self, typ: &str, locales: &[S]) -> Option<&str> {
+        let typ = self.canonical(typ);
+
+        let ti = self.type_info.get(typ)?;
+        locales
+            .iter()
+            .flat_map(|l| locale_fallbacks(l.as_ref()))
+            .find_map(|lang| ti.localized_description(&lang))
+            .or(ti.description.as_deref())
+    }
+
+    /// Get the description of a type in the current process's locale.
+    ///
+    /// The locale is determined from `LANGUAGE`, `LC_ALL`, `LC_MESSAGES` and `LANG`.
+    pub fn description_for_current_locale(&self, typ: &str) -> Option<&str> {
+        self.description_for_locale(typ, &current_languages())
+    }
+
+    /// Get the icon name for a type.
+    ///
+    /// If the database does not specify an icon, this is the type name with `/`
+    /// replaced by `-` (e.g. `image-png`).
+    pub fn icon_name(&self, typ: &str) -> String {
+        let typ = self.canonical(typ);
+
         self.type_info
             .get(typ)
-            .and_then(|ti| ti.description.as_ref())
-            .map(|s| s.as_str())
+            .and_then(|ti| ti.icon.as_ref())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| typ.replace('/', "-"))
+    }
+
+    /// Get the generic icon name for a type.
+    ///
+    /// If neither the type nor any of its supertypes specify a generic icon,
+    /// this is the media type followed by `-x-generic` (e.g. `image-x-generic`).
+    pub fn generic_icon_name(&self, typ: &str) -> String {
+        let typ = self.canonical(typ);
+
+        self.supertypes(typ)
+            .iter()
+            .find_map(|st| {
+                self.type_info
+                    .get(st)
+                    .and_then(|ti| ti.generic_icon.as_ref())
+                    .map(|s| s.to_string())
+            })
+            .unwrap_or_else(|| {
+                let media = typ.split_once('/').map_or(typ, |(m, _)| m);
+                format!("{}-x-generic", media)
+            })
+    }
+
+    /// Get the icon names to try for a type, in order of preference.
+    ///
+    /// This is the type's icon, then its generic icon, then the icons of its
+    /// supertypes as fallbacks.
+    pub fn icon_names(&self, typ: &str) -> Vec<String> {
+        let mut names = vec![self.icon_name(typ)];
+        let generic = self.generic_icon_name(typ);
+        if !names.contains(&generic) {
+            names.push(generic);
+        }
+        for st in self.supertypes(typ) {
+            let name = self.icon_name(&st);
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// Get the acronym for a type.
+    pub fn acronym(&self, typ: &str) -> Option<&str> {
+        let typ = self.canonical(typ);
+
+        self.type_info.get(typ).and_then(|ti| ti.acronym.as_deref())
+    }
+
+    /// Get the expanded acronym for a type.
+    pub fn expanded_acronym(&self, typ: &str) -> Option<&str> {
+        let typ = self.canonical(typ);
+
+        self.type_info
+            .get(typ)
+            .and_then(|ti| ti.expanded_acronym.as_deref())
     }
 
     /// Get the aliases of a type.
     pub fn aliases(&self, typ: &str) -> Vec<&str> {
+        let typ = self.canonical(typ);
+
         if let Some(ti) = self.type_info.get(typ) {
             ti.aliases.iter().map(|cs| cs.as_ref()).collect()
         } else {
@@ -90,6 +379,8 @@ impl MimeDB {
 
     /// Get the parents of a type.
     pub fn parents(&self, typ: &str) -> Vec<&str> {
+        let typ = self.canonical(typ);
+
         if let Some(ti) = self.type_info.get(typ) {
             ti.parents.iter().map(|cs| cs.as_ref()).collect()
         } else {
@@ -101,12 +392,13 @@ impl MimeDB {
     ///
     /// Types are in discovery order, so closer supertypes are at the beginning of the list.
     pub fn supertypes(&self, typ: &str) -> Vec<CachedString> {
+        let typ = self.canonical(typ);
         let mut types = Vec::new();
         let mut queue: SearchQueue<CachedString> = SearchQueue::new();
         let mut is_text = false;
 
         // start the queue with the search type
-        queue.maybe_add(self.names.cache(typ));
+        queue.maybe_add(self.names.lookup(typ));
 
         // pump until all types are done
         while let Some(qt) = queue.get() {
@@ -120,17 +412,17 @@ impl MimeDB {
 
             if let Some(info) = self.type_info.get(qt.as_ref()) {
                 for st in info.parents.iter() {
-                    queue.maybe_add(st.clone());
+                    queue.maybe_add(self.canonical_name(st));
                 }
             }
         }
 
         // add default parent relationships
         if is_text && !queue.saw("text/plain") {
-            types.push(self.names.cache("text/plain"));
+            types.push(self.names.lookup("text/plain"));
         }
         if !typ.starts_with("inode/") && !queue.saw("application/octet-stream") {
-            types.push(self.names.cache("application/octet-stream"));
+            types.push(self.names.lookup("application/octet-stream"));
         }
 
         types
@@ -147,3 +439,91 @@ impl MimeDB {
         }
     }
 }
+
+#[test]
+fn test_inherited_generic_icon() {
+    use crate::record::MimeTypeRecord;
+
+    let mut db = MimeDB::new();
+    db.add_records(vec![
+        MimeTypeRecord {
+            name: "application/x-hackem".into(),
+            icon: Some("hackem".into()),
+            generic_icon: Some("x-office-document".into()),
+            ..Default::default()
+        },
+        MimeTypeRecord {
+            name: "application/x-hackem-muche".into(),
+            superclasses: vec!["application/x-hackem".into()],
+            ..Default::default()
+        },
+    ]);
+    assert_eq!(db.icon_name("application/x-hackem"), "hackem");
+    assert_eq!(
+        db.icon_name("application/x-hackem-muche"),
+        "application-x-hackem-muche"
+    );
+    assert_eq!(
+        db.generic_icon_name("application/x-hackem-muche"),
+        "x-office-document"
+    );
+    assert_eq!(
+        db.icon_names("application/x-hackem-muche"),
+        vec![
+            "application-x-hackem-muche",
+            "x-office-document",
+            "hackem",
+            "application-octet-stream"
+        ]
+    );
+}
+
+#[test]
+fn test_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<MimeDB>();
+}
+
+#[test]
+fn test_aliases() {
+    use crate::record::MimeTypeRecord;
+
+    let mut db = MimeDB::new();
+    db.add_records(vec![
+        MimeTypeRecord {
+            name: "text/javascript".into(),
+            description: Some("JavaScript program".into()),
+            aliases: vec!["application/x-javascript".into()],
+            superclasses: vec!["application/x-executable".into()],
+            ..Default::default()
+        },
+        MimeTypeRecord {
+            name: "application/x-executable".into(),
+            aliases: vec!["application/x-exe".into()],
+            ..Default::default()
+        },
+    ]);
+    assert!(db.is_alias("application/x-javascript"));
+    assert!(!db.is_alias("text/javascript"));
+    assert_eq!(db.canonical("application/x-javascript"), "text/javascript");
+    assert_eq!(db.canonical("text/x-hackem"), "text/x-hackem");
+    assert_eq!(
+        db.description("application/x-javascript"),
+        Some("JavaScript program")
+    );
+    assert!(db.is_subtype("application/x-javascript", "application/x-exe"));
+    assert!(db.is_subtype("text/javascript", "text/plain"));
+    assert_eq!(
+        db.supertypes("application/x-javascript"),
+        vec![
+            "text/javascript",
+            "application/x-executable",
+            "text/plain",
+            "application/octet-stream"
+        ]
+    );
+    assert_eq!(
+        db.aliases("application/x-javascript"),
+        vec!["application/x-javascript"]
+    );
+}
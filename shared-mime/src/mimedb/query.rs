@@ -1,15 +1,40 @@
 //! MIME DB querying implementation.
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::ffi::OsStr;
 use std::fs::Metadata;
 #[cfg(unix)]
 use std::os::unix::fs::FileTypeExt;
 use std::os::unix::fs::MetadataExt;
+use std::path::Path;
 
 use log::*;
 
-use crate::{query::FileQuery, Answer, QueryError};
+use crate::{
+    answer::{Found, MatchSource},
+    fnmatch::{fold_case, MatchRule},
+    query::FileQuery,
+    xml::find_root,
+    Answer, QueryError,
+};
 
-use super::MimeDB;
+use super::{
+    explain::{ExplainStep, Trace},
+    GlobRule, MimeDB,
+};
+
+/// Magic rules with at least this priority are checked before globs.
+pub const MAGIC_HIGH_PRIORITY: u32 = 80;
+
+/// Rank a glob among the globs matching a file name.
+///
+/// Literal globs beat patterns, then globs from later packages win, then
+/// higher weights, and then longer patterns (so `*.tar.gz` beats `*.gz`).
+fn glob_rank(glob: &GlobRule) -> (bool, i32, i32, usize) {
+    let rule = &glob.matcher.rule;
+    let literal = matches!(rule, MatchRule::Literal(_));
+    (literal, glob.sequence, glob.weight, rule.pattern_len())
+}
 
 enum MetaAnswer {
     Inode(&'static str),
@@ -40,7 +65,16 @@ fn type_for_meta(meta: &Metadata) -> MetaAnswer {
 
 impl MimeDB {
     /// Query the MIME database.
+    ///
+    /// This follows the order in the specification: file metadata, then
+    /// high-priority magic, then globs, then low-priority magic, and finally
+    /// the empty-file and text/binary fallbacks.  XML documents are refined
+    /// using their root element.
     pub fn query(&self, query: &FileQuery<'_>) -> Result<Answer<'_>, QueryError> {
+        Ok(self.query_traced(query, &mut Trace::off()))
+    }
+
+    pub(super) fn query_traced(&self, query: &FileQuery<'_>, trace: &mut Trace) -> Answer<'_> {
         let dbg_name = if let Some(name) = query.filename {
             name.to_string_lossy()
         } else {
@@ -51,30 +85,171 @@ impl MimeDB {
             debug!("{}: looking up with metadata", dbg_name);
             match type_for_meta(meta) {
                 // if we have a special title, all done!
-                MetaAnswer::Inode(tstr) => return Ok(Answer::definite(tstr)),
-                MetaAnswer::File(size) => Some(size),
+                MetaAnswer::Inode(tstr) => {
+                    trace.note(|| ExplainStep::Metadata {
+                        mimetype: Some(tstr.into()),
+                        size: None,
+                    });
+                    return Answer::definite(tstr, MatchSource::Metadata);
+                }
+                MetaAnswer::File(size) => {
+                    trace.note(|| ExplainStep::Metadata {
+                        mimetype: None,
+                        size: Some(size),
+                    });
+                    Some(size)
+                }
             }
         } else {
             None
         };
 
-        // next step: look up based on filename
+        // look up based on filename
         let mut ans = Answer::unknown();
         if let Some(name) = query.filename {
             debug!("{}: looking up with file name", dbg_name);
-            ans = self.query_filename(name);
+            ans = self.filename_traced(name, trace);
+        }
+
+        // and based on content
+        let magic = if let Some(data) = &query.content {
+            debug!(
+                "{}: looking up with {} bytes of content",
+                dbg_name,
+                data.len()
+            );
+            self.match_magic(data)
+        } else {
+            None
+        };
+
+        // high-priority magic overrides globs, low-priority only fills in
+        if let Some((prio, found)) = magic {
+            trace.note(|| ExplainStep::Magic {
+                priority: prio,
+                mimetypes: found.iter().map(|f| f.mimetype.into()).collect(),
+            });
+            if prio >= MAGIC_HIGH_PRIORITY || !ans.is_definite() {
+                ans = self.resolve_magic(&dbg_name, ans, found, prio >= MAGIC_HIGH_PRIORITY, trace);
+            }
+        }
+
+        // XML documents can be refined by their root element
+        if let Some(data) = &query.content {
+            let is_xml = ans
+                .all_types()
+                .iter()
+                .any(|t| self.is_subtype(t, "application/xml"));
+            if is_xml {
+                let xans = self.query_xml(data);
+                trace.note(|| ExplainStep::XmlRoot {
+                    mimetype: xans.best().map(|t| t.into()),
+                });
+                if xans.is_definite() {
+                    debug!("{}: XML root identifies {:?}", dbg_name, xans.best());
+                    ans = xans;
+                }
+            }
         }
 
         if ans.is_unknown() && size == Some(0) {
-            ans = Answer::definite("application/x-zerosize")
+            ans = Answer::definite("application/x-zerosize", MatchSource::ZeroSize)
+        }
+
+        if ans.is_unknown() {
+            if let Some(data) = &query.content {
+                if self.text_rules.is_text(data) {
+                    debug!("{}: content looks like text", dbg_name);
+                    ans = Answer::definite("text/plain", MatchSource::Text)
+                }
+            }
         }
 
-        // TODO: detect text files
         if ans.is_unknown() {
-            ans = Answer::definite("application/octet-stream")
+            ans = Answer::definite("application/octet-stream", MatchSource::Default)
         }
 
-        Ok(ans)
+        if let [Found {
+            mimetype,
+            source: MatchSource::ZeroSize | MatchSource::Text | MatchSource::Default,
+            ..
+        }] = ans.found()
+        {
+            trace.note(|| ExplainStep::Fallback {
+                mimetype: mimetype.to_string(),
+            });
+        }
+
+        ans
+    }
+
+    /// Query the MIME database for a file path.
+    ///
+    /// This reads enough of the file's content to evaluate all magic rules and
+    /// the text detection rules.
+    pub fn query_path<P: AsRef<Path>>(&self, path: P) -> Result<Answer<'_>, QueryError> {
+        let query = FileQuery::for_path_in(path.as_ref(), self)?;
+        self.query(&query)
+    }
+
+    /// Get the number of leading bytes of a file that [MimeDB::query_path]
+    /// reads, enough for the magic rules and text detection.
+    pub fn header_len(&self) -> usize {
+        let mut len = self.magic_extent();
+        if self.text_rules.enabled {
+            len = len.max(self.text_rules.window);
+        }
+        len
+    }
+
+    /// Combine glob results with magic results.
+    ///
+    /// Glob results that are (subtypes of) magic results are preferred, then
+    /// magic results that refine the glob results.  Otherwise, the magic results
+    /// are used if they take precedence or there are no glob results.
+    fn resolve_magic<'a>(
+        &'a self,
+        name: &str,
+        globbed: Answer<'a>,
+        mut magic: Vec<Found<'a>>,
+        magic_first: bool,
+        trace: &mut Trace,
+    ) -> Answer<'a> {
+        let gtypes: Vec<&str> = globbed.all_types().to_vec();
+        let mtypes: Vec<&str> = magic.iter().map(|f| f.mimetype).collect();
+        let mut refined: Vec<Found<'a>> = globbed
+            .clone()
+            .into_found()
+            .into_iter()
+            .filter(|g| mtypes.iter().any(|m| self.is_subtype(g.mimetype, m)))
+            .collect();
+        if refined.is_empty() {
+            refined = magic
+                .iter()
+                .filter(|m| gtypes.iter().any(|g| self.is_subtype(m.mimetype, g)))
+                .cloned()
+                .collect();
+        }
+        if !refined.is_empty() {
+            debug!("{}: magic and globs agree on {:?}", name, refined);
+            trace.note(|| ExplainStep::ResolveMagic {
+                mimetypes: refined.iter().map(|f| f.mimetype.into()).collect(),
+            });
+            let ambiguous = self.coalesce_matches(name, &mut refined, trace);
+            Answer::new(refined, ambiguous)
+        } else if magic_first || globbed.is_unknown() {
+            debug!("{}: using magic types {:?}", name, mtypes);
+            trace.note(|| ExplainStep::ResolveMagic {
+                mimetypes: mtypes.iter().map(|t| t.to_string()).collect(),
+            });
+            let ambiguous = self.coalesce_matches(name, &mut magic, trace);
+            Answer::new(magic, ambiguous)
+        } else {
+            trace.note(|| ExplainStep::ResolveMagic {
+                mimetypes: gtypes.iter().map(|t| t.to_string()).collect(),
+            });
+            globbed
+        }
     }
 
     /// Use metadata to detect file types.
@@ -82,53 +257,267 @@ impl MimeDB {
     /// This function can only detect the `inode/` types and `application/octet-stream`.
     pub fn query_meta(&self, meta: &Metadata) -> Answer<'_> {
         match type_for_meta(meta) {
-            MetaAnswer::Inode(mt) => Answer::definite(mt),
-            MetaAnswer::File(_) => Answer::definite("application/octet-stream"),
+            MetaAnswer::Inode(mt) => Answer::definite(mt, MatchSource::Metadata),
+            MetaAnswer::File(_) => {
+                Answer::definite("application/octet-stream", MatchSource::Default)
+            }
         }
     }
 
     /// Look up MIME type information based only on a filename.
     pub fn query_filename<S: AsRef<OsStr>>(&self, name: S) -> Answer<'_> {
-        let name = name.as_ref();
+        self.filename_traced(name.as_ref(), &mut Trace::off())
+    }
+
+    fn filename_traced(&self, name: &OsStr, trace: &mut Trace) -> Answer<'_> {
         let display = name.to_string_lossy();
         debug!("looking up filename {}", display);
-        let mut sw = None;
+        let mut best = None;
         let mut matches = Vec::new();
         let pbs = name.as_encoded_bytes();
-        for glob in self.globs.iter() {
-            if let Some((s, w)) = sw {
-                if s > glob.sequence || w > glob.weight {
-                    // done searching
+        // fold the name once, rather than for every case-insensitive glob
+        let lname = fold_case(pbs);
+        let candidates = self.glob_index.candidates(&lname);
+        for i in candidates.literals() {
+            self.check_glob(i, pbs, &lname, &mut best, &mut matches, trace);
+        }
+        // literal matches outrank all others, so there is no need to look further
+        if best.is_none() {
+            for i in candidates.others() {
+                self.check_glob(i, pbs, &lname, &mut best, &mut matches, trace);
+            }
+        }
+        if let Some((literal, sequence, weight, pattern_len)) = best {
+            trace.note(|| ExplainStep::GlobSelected {
+                literal,
+                sequence,
+                weight,
+                pattern_len,
+                mimetypes: matches.iter().map(|f| f.mimetype.into()).collect(),
+            });
+        }
+        let ambiguous = self.coalesce_matches(&display, &mut matches, trace);
+        Answer::new(matches, ambiguous)
+    }
+
+    /// Check a candidate glob against a file name (and its folded form),
+    /// keeping it if it ranks at least as well as the best match so far.
+    fn check_glob<'a>(
+        &'a self,
+        i: usize,
+        name: &[u8],
+        lname: &[u8],
+        best: &mut Option<(bool, i32, i32, usize)>,
+        matches: &mut Vec<Found<'a>>,
+        trace: &mut Trace,
+    ) {
+        let glob = &self.globs[i];
+        if !glob.matcher.matches_folded(name, lname) {
+            return;
+        }
+        trace.note(|| ExplainStep::GlobMatch {
+            pattern: glob.pattern.to_string(),
+            mimetype: glob.mimetype.to_string(),
+            sequence: glob.sequence,
+            weight: glob.weight,
+        });
+        let rank = Some(glob_rank(glob));
+        match rank.cmp(best) {
+            Ordering::Greater => {
+                *best = rank;
+                matches.clear();
+                matches.push(self.glob_found(glob));
+            }
+            Ordering::Equal if !matches.iter().any(|f| glob.mimetype == f.mimetype) => {
+                matches.push(self.glob_found(glob));
+            }
+            _ => (),
+        }
+    }
+
+    /// Look up MIME type information based only on file contents.
+    ///
+    /// The data should contain the beginning of the file; only the magic rules
+    /// with the highest matching priority are used.
+    pub fn query_magic(&self, data: &[u8]) -> Answer<'_> {
+        debug!("looking up {} bytes of data", data.len());
+        if let Some((_prio, mut matches)) = self.match_magic(data) {
+            let ambiguous = self.coalesce_matches("⟨data⟩", &mut matches, &mut Trace::off());
+            Answer::new(matches, ambiguous)
+        } else {
+            Answer::unknown()
+        }
+    }
+
+    /// Look up the type of an XML document from its root element.
+    ///
+    /// The data should contain the beginning of the document.
+    pub fn query_xml(&self, data: &[u8]) -> Answer<'_> {
+        let Some(root) = find_root(data) else {
+            return Answer::unknown();
+        };
+        trace!(
+            "XML root element {{{}}}{}",
+            root.namespace_uri,
+            root.local_name
+        );
+        let key = format!("{} {}", root.namespace_uri, root.local_name);
+        match self.root_xml.get(key.as_str()) {
+            Some((mt, seq)) => {
+                let source = MatchSource::RootXml {
+                    namespace: Cow::Owned(root.namespace_uri),
+                    local_name: Cow::Owned(root.local_name),
+                };
+                Answer::new(vec![Found::new(mt, source, self.origin(*seq))], false)
+            }
+            None => Answer::unknown(),
+        }
+    }
+
+    fn glob_found<'a>(&'a self, glob: &'a GlobRule) -> Found<'a> {
+        let source = MatchSource::Glob {
+            pattern: Cow::Borrowed(&glob.pattern),
+            weight: glob.weight,
+        };
+        Found::new(glob.mimetype.as_str(), source, self.origin(glob.sequence))
+    }
+
+    /// Find the highest-priority magic matches, returning the priority and types.
+    fn match_magic(&self, data: &[u8]) -> Option<(u32, Vec<Found<'_>>)> {
+        let mut prio = None;
+        let mut matches = Vec::new();
+        for rule in self.magic.iter() {
+            if let Some(p) = prio {
+                if p > rule.priority {
                     break;
                 }
             }
-            if glob.matcher.matches(pbs) {
-                sw = Some((glob.sequence, glob.weight));
-                matches.push(glob.mimetype.as_str());
+            if let Some(m) = rule.matcher.find(data) {
+                trace!(
+                    "data matches {} (priority {})",
+                    rule.mimetype,
+                    rule.priority
+                );
+                prio = Some(rule.priority);
+                if !matches.iter().any(|f: &Found| rule.mimetype == f.mimetype) {
+                    let source = MatchSource::Magic {
+                        priority: rule.priority,
+                        offset: m.offset,
+                        value: Cow::Borrowed(m.value.as_ref()),
+                    };
+                    let origin = self.origin(rule.sequence);
+                    matches.push(Found::new(rule.mimetype.as_str(), source, origin));
+                }
             }
         }
-        let ambiguous = self.coalesce_fn_matches(&display, &mut matches);
-        Answer::new(matches, ambiguous)
+        prio.map(|p| (p, matches))
     }
 
-    fn coalesce_fn_matches(&self, name: &str, matches: &mut Vec<&str>) -> bool {
+    fn coalesce_matches(&self, name: &str, matches: &mut [Found<'_>], trace: &mut Trace) -> bool {
         let mut ambiguous = matches.len() > 1;
-        // TODO: prefer matching literals
-        // TODO: disambiguate by match length
-        if ambiguous {
+        if ambiguous && self.collapse_supertypes {
             // this is our own addition to the match logic
             // if we have multiple matches, but one is the supertype of the others, use it
             debug!("{}: {} matches, sorting", name, matches.len());
             // put supertype first
-            matches.sort_by(|a, b| self.compare_types(a, b).reverse());
-            let root = matches[0];
-            ambiguous = !matches[1..].iter().all(|t| self.is_subtype(t, root));
+            matches.sort_by(|a, b| self.compare_types(a.mimetype, b.mimetype).reverse());
+            let root = matches[0].mimetype;
+            ambiguous = !matches[1..]
+                .iter()
+                .all(|t| self.is_subtype(t.mimetype, root));
             if ambiguous {
                 debug!("{}: ambiguous match", name)
             } else {
                 debug!("{}: best match {}", name, root)
             }
         }
+        if matches.len() > 1 {
+            trace.note(|| ExplainStep::Coalesce {
+                mimetypes: matches.iter().map(|f| f.mimetype.into()).collect(),
+                collapsed: self.collapse_supertypes && !ambiguous,
+                ambiguous,
+            });
+        }
         ambiguous
     }
 }
+
+#[cfg(test)]
+use crate::record::glob_record;
+
+#[test]
+fn test_longest_glob() {
+    let mut db = MimeDB::new();
+    db.set_collapse_supertypes(false);
+    db.add_records(vec![
+        glob_record("application/gzip", &[("*.gz", 50)]),
+        glob_record("application/x-compressed-tar", &[("*.tar.gz", 50)]),
+    ]);
+    let ans = db.query_filename("a.tar.gz");
+    assert_eq!(ans.all_types(), vec!["application/x-compressed-tar"]);
+    assert_eq!(db.query_filename("a.gz").best(), Some("application/gzip"));
+}
+
+#[test]
+fn test_weight_beats_length() {
+    let mut db = MimeDB::new();
+    db.add_records(vec![
+        glob_record("application/gzip", &[("*.gz", 60)]),
+        glob_record("application/x-compressed-tar", &[("*.tar.gz", 50)]),
+    ]);
+    let ans = db.query_filename("a.tar.gz");
+    assert_eq!(ans.best(), Some("application/gzip"));
+}
+
+#[test]
+fn test_literal_glob() {
+    let mut db = MimeDB::new();
+    db.add_records(vec![
+        glob_record("text/x-makefile", &[("makefile", 10)]),
+        glob_record("text/x-hackem", &[("*file", 90)]),
+    ]);
+    assert_eq!(
+        db.query_filename("Makefile").best(),
+        Some("text/x-makefile")
+    );
+    // the other globs are not checked once a literal matches
+    let query = FileQuery::builder()
+        .filename(OsStr::new("Makefile"))
+        .build();
+    let steps = db.explain(&query).steps;
+    let matched = steps
+        .iter()
+        .filter(|s| matches!(s, ExplainStep::GlobMatch { .. }))
+        .count();
+    assert_eq!(matched, 1);
+    assert!(steps.contains(&ExplainStep::GlobSelected {
+        literal: true,
+        sequence: 1,
+        weight: 10,
+        pattern_len: 8,
+        mimetypes: vec!["text/x-makefile".into()],
+    }));
+}
+
+#[test]
+fn test_collapse_supertypes() {
+    let mut db = MimeDB::new();
+    let mut child = glob_record("text/x-hackem-muche", &[("*.hkm", 50)]);
+    child.superclasses.push("text/x-hackem".into());
+    db.add_records(vec![glob_record("text/x-hackem", &[("*.hkm", 50)]), child]);
+    assert_eq!(db.query_filename("a.hkm").best(), Some("text/x-hackem"));
+    db.set_collapse_supertypes(false);
+    assert!(db.query_filename("a.hkm").is_ambiguous());
+}
+
+#[test]
+fn test_unicode_glob() {
+    let mut db = MimeDB::new();
+    db.add_records(vec![
+        glob_record("text/x-hackem", &[("*.ХАК", 50)]),
+        glob_record("text/x-muche", &[("Муче", 50)]),
+    ]);
+    assert_eq!(db.query_filename("файл.хак").best(), Some("text/x-hackem"));
+    assert_eq!(db.query_filename("ФАЙЛ.Хак").best(), Some("text/x-hackem"));
+    assert_eq!(db.query_filename("МУЧЕ").best(), Some("text/x-muche"));
+}
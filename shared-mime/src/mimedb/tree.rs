@@ -0,0 +1,171 @@
+//! Tree magic: content types for directories and mounted volumes.
+use std::borrow::Cow;
+use std::fs::{self, Metadata};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use log::*;
+
+use crate::{
+    answer::{Found, MatchSource},
+    record::TreeFileType,
+    strcache::CachedString,
+    Answer, QueryError,
+};
+
+use super::MimeDB;
+
+/// A compiled tree magic match (see [TreeMatch](crate::record::TreeMatch)).
+#[derive(Debug, Clone)]
+pub struct TreeMatcher {
+    pub path: Cow<'static, str>,
+    pub file_type: Option<TreeFileType>,
+    pub match_case: bool,
+    pub executable: bool,
+    pub non_empty: bool,
+    pub mimetype: Option<CachedString>,
+    pub children: Cow<'static, [TreeMatcher]>,
+}
+
+impl MimeDB {
+    /// Look up the content types (`x-content/*`) of a directory tree.
+    ///
+    /// This is intended for the root of a mounted volume or similar directory,
+    /// and returns all matching types, with the highest-priority types first.
+    pub fn query_tree<P: AsRef<Path>>(&self, root: P) -> Result<Answer<'_>, QueryError> {
+        let root = root.as_ref();
+        debug!("{}: looking up tree magic", root.display());
+        let mut types = Vec::new();
+        for rule in self.treemagic.iter() {
+            if types.iter().any(|f: &Found| rule.mimetype == f.mimetype) {
+                continue;
+            }
+            for tm in rule.matches.iter() {
+                if self.tree_matches(root, tm) {
+                    debug!(
+                        "{}: matches {} (priority {})",
+                        root.display(),
+                        rule.mimetype,
+                        rule.priority
+                    );
+                    let source = MatchSource::TreeMagic {
+                        priority: rule.priority,
+                    };
+                    let origin = self.origin(rule.sequence);
+                    types.push(Found::new(rule.mimetype.as_str(), source, origin));
+                    break;
+                }
+            }
+        }
+        Ok(Answer::new(types, false))
+    }
+
+    /// Check a tree match.  Entries that cannot be read do not match.
+    fn tree_matches(&self, root: &Path, tm: &TreeMatcher) -> bool {
+        let Some(path) = resolve_path(root, &tm.path, tm.match_case) else {
+            return false;
+        };
+        trace!("checking {}", path.display());
+        let lmeta = match fs::symlink_metadata(&path) {
+            Ok(m) => m,
+            Err(e) => {
+                debug!("{}: cannot read metadata: {}", path.display(), e);
+                return false;
+            }
+        };
+        let meta = if lmeta.is_symlink() {
+            match fs::metadata(&path) {
+                Ok(m) => m,
+                // a dangling link can still match type="link"
+                Err(_) => lmeta.clone(),
+            }
+        } else {
+            lmeta.clone()
+        };
+
+        let type_ok = match tm.file_type {
+            None => true,
+            Some(TreeFileType::Link) => lmeta.is_symlink(),
+            Some(TreeFileType::File) => meta.is_file(),
+            Some(TreeFileType::Directory) => meta.is_dir(),
+        };
+        if !type_ok {
+            return false;
+        }
+        if tm.executable && !is_executable(&meta) {
+            return false;
+        }
+        if tm.non_empty && is_empty(&path, &meta) {
+            return false;
+        }
+        if let Some(mt) = &tm.mimetype {
+            let ans = match self.query_path(&path) {
+                Ok(ans) => ans,
+                Err(e) => {
+                    debug!("{}: cannot query type: {}", path.display(), e);
+                    return false;
+                }
+            };
+            if !ans.all_types().iter().any(|t| self.is_subtype(t, mt)) {
+                return false;
+            }
+        }
+
+        tm.children.is_empty() || tm.children.iter().any(|c| self.tree_matches(root, c))
+    }
+}
+
+/// Resolve a relative path under a root, optionally ignoring case.
+fn resolve_path(root: &Path, rel: &str, match_case: bool) -> Option<PathBuf> {
+    let mut path = root.to_path_buf();
+    for part in rel.split('/').filter(|p| !p.is_empty()) {
+        let exact = path.join(part);
+        if exact.symlink_metadata().is_ok() {
+            path = exact;
+            continue;
+        }
+        if match_case || !path.is_dir() {
+            return None;
+        }
+        let lpart = part.to_lowercase();
+        let entries = match fs::read_dir(&path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                debug!("{}: cannot read directory: {}", path.display(), e);
+                return None;
+            }
+        };
+        path = entries
+            .filter_map(|e| e.ok())
+            .find(|e| e.file_name().to_string_lossy().to_lowercase() == lpart)?
+            .path();
+    }
+    Some(path)
+}
+
+#[cfg(unix)]
+fn is_executable(meta: &Metadata) -> bool {
+    meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_meta: &Metadata) -> bool {
+    true
+}
+
+/// Check whether a file or directory is empty, treating unreadable
+/// directories as empty.
+fn is_empty(path: &Path, meta: &Metadata) -> bool {
+    if meta.is_dir() {
+        match fs::read_dir(path) {
+            Ok(mut entries) => entries.next().is_none(),
+            Err(e) => {
+                debug!("{}: cannot read directory: {}", path.display(), e);
+                true
+            }
+        }
+    } else {
+        meta.len() == 0
+    }
+}
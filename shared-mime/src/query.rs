@@ -1,13 +1,16 @@
 use std::{
     ffi::OsStr,
-    fs::{self, Metadata},
-    io::ErrorKind,
+    fs::{self, File, Metadata},
+    io::{ErrorKind, Read},
     path::Path,
 };
 
 use log::trace;
 
-use crate::QueryError;
+use crate::{MimeDB, QueryError};
+
+/// Default number of bytes read from the start of a file by [FileQuery::for_path].
+pub const DEFAULT_HEADER_LEN: usize = 4096;
 
 /// Information avaialble for a query to the database.
 pub struct FileQuery<'a> {
@@ -15,6 +18,8 @@ pub struct FileQuery<'a> {
     pub(crate) filename: Option<&'a OsStr>,
     /// The file metadata.
     pub(crate) metadata: Option<Metadata>,
+    /// The beginning of the file's content.
+    pub(crate) content: Option<Vec<u8>>,
 }
 
 /// Builder for [FileQuery].
@@ -24,6 +29,8 @@ pub struct FileQueryBuilder<'name> {
     filename: Option<&'name OsStr>,
     /// The file metadata.
     metadata: Option<Metadata>,
+    /// The beginning of the file's content.
+    content: Option<Vec<u8>>,
 }
 
 impl FileQuery<'_> {
@@ -37,7 +44,26 @@ impl<'name> FileQuery<'name> {
         FileQuery::builder().filename(name).build()
     }
 
+    /// Create a query for a path, reading the first [DEFAULT_HEADER_LEN] bytes
+    /// of a regular file.
+    ///
+    /// Use [FileQuery::for_path_in] to read as many bytes as a database's
+    /// magic rules need.
     pub fn for_path(path: &'name Path) -> Result<FileQuery<'name>, QueryError> {
+        Self::for_path_with_header(path, DEFAULT_HEADER_LEN)
+    }
+
+    /// Create a query for a path, reading as many bytes of a regular file as
+    /// the database needs (see [MimeDB::header_len]).
+    pub fn for_path_in(path: &'name Path, db: &MimeDB) -> Result<FileQuery<'name>, QueryError> {
+        Self::for_path_with_header(path, db.header_len())
+    }
+
+    /// Create a query for a path, reading up to `header_len` bytes of a regular file.
+    pub fn for_path_with_header(
+        path: &'name Path,
+        header_len: usize,
+    ) -> Result<FileQuery<'name>, QueryError> {
         let mut fqb = Self::builder();
 
         if let Some(name) = path.file_name() {
@@ -47,7 +73,14 @@ impl<'name> FileQuery<'name> {
 
         trace!("{}: looking up metadata", path.display());
         match fs::metadata(path) {
-            Ok(meta) => fqb = fqb.metadata(meta),
+            Ok(meta) => {
+                if meta.is_file() && header_len > 0 {
+                    if let Some(header) = read_header(path, header_len)? {
+                        fqb = fqb.content(&header);
+                    }
+                }
+                fqb = fqb.metadata(meta)
+            }
             Err(e) if e.kind() == ErrorKind::NotFound => {
                 trace!("{}: file not found", path.display());
             }
@@ -70,6 +103,7 @@ impl<'name> FileQueryBuilder<'name> {
         FileQuery {
             filename: self.filename,
             metadata: self.metadata,
+            content: self.content,
         }
     }
 
@@ -88,4 +122,28 @@ impl<'name> FileQueryBuilder<'name> {
             ..self
         }
     }
+
+    /// Set the file content (or its beginning) for this builder.
+    pub fn content(self, data: &[u8]) -> FileQueryBuilder<'name> {
+        FileQueryBuilder {
+            content: Some(data.to_vec()),
+            ..self
+        }
+    }
+}
+
+/// Read the beginning of a file, returning [None] if it cannot be read.
+fn read_header(path: &Path, len: usize) -> Result<Option<Vec<u8>>, QueryError> {
+    trace!("{}: reading {} header bytes", path.display(), len);
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            trace!("{}: cannot read file", path.display());
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut buf = Vec::with_capacity(len.min(64 * 1024));
+    file.take(len as u64).read_to_end(&mut buf)?;
+    Ok(Some(buf))
 }
@@ -4,21 +4,141 @@
 //! mime info files (with [crate::runtime]) and the actual MIME type lookup
 //! structures.  Serializing these records are an effective way to cache parsed
 //! MIME data.
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 /// A MIME type record from the shared mime database.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+///
+/// Fields missing from serialized records take their default values, so
+/// records cached by older versions can still be read.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
 pub struct MimeTypeRecord {
     /// The full MIME type (type/subtype).
     pub name: String,
     /// The string description of this record.
     pub description: Option<String>,
+    /// Translated descriptions, keyed by language (e.g. `pt_BR`).
+    pub localized_descriptions: BTreeMap<String, String>,
+    /// The icon name, if different from the default.
+    pub icon: Option<String>,
+    /// The generic icon name, if different from the default.
+    pub generic_icon: Option<String>,
+    /// The acronym for this type (e.g. `PNG`).
+    pub acronym: Option<String>,
+    /// The expanded acronym (e.g. `Portable Network Graphics`).
+    pub expanded_acronym: Option<String>,
     /// List of globs (with priorities) for the record.
     pub globs: Vec<GlobRule>,
+    /// Whether this record replaces globs from previously-loaded packages.
+    pub glob_deleteall: bool,
     /// List of this record's immediate superclasses.
     pub superclasses: Vec<String>,
     /// Aliases for this record.
     pub aliases: Vec<String>,
+    /// Magic rules for detecting this type from file content.
+    pub magic: Vec<MagicRule>,
+    /// Whether this record replaces magic from previously-loaded packages.
+    pub magic_deleteall: bool,
+    /// Tree magic rules for detecting this type from a directory's contents.
+    pub treemagic: Vec<TreeMagicRule>,
+    /// XML root elements that identify this type.
+    pub root_xml: Vec<RootXmlRule>,
+    /// Elements of the type definition that this crate does not interpret.
+    pub extensions: Vec<ExtensionElement>,
+}
+
+impl MimeTypeRecord {
+    /// Get the uninterpreted elements with a given name (such as `x-kde-foo`).
+    pub fn extensions_named<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a ExtensionElement> + 'a {
+        self.extensions.iter().filter(move |e| e.name == name)
+    }
+
+    /// Merge a later record for the same type into this one.
+    ///
+    /// This is how later packages extend a type, both when merging the
+    /// packages of a directory and when adding records to a
+    /// [MimeDB](crate::MimeDB).  A new description replaces the old one and
+    /// its translations; other single values are replaced if set, and lists
+    /// are extended without duplicates.  Glob and magic deletions discard the
+    /// rules merged so far, and a glob replaces an earlier one with the same
+    /// pattern.
+    pub fn merge(&mut self, rec: MimeTypeRecord) {
+        if let Some(desc) = rec.description {
+            self.description = Some(desc);
+            self.localized_descriptions = rec.localized_descriptions;
+        } else {
+            self.localized_descriptions
+                .extend(rec.localized_descriptions);
+        }
+        if rec.icon.is_some() {
+            self.icon = rec.icon;
+        }
+        if rec.generic_icon.is_some() {
+            self.generic_icon = rec.generic_icon;
+        }
+        if rec.acronym.is_some() {
+            self.acronym = rec.acronym;
+        }
+        if rec.expanded_acronym.is_some() {
+            self.expanded_acronym = rec.expanded_acronym;
+        }
+        for alias in rec.aliases {
+            if !self.aliases.contains(&alias) {
+                self.aliases.push(alias);
+            }
+        }
+        for sup in rec.superclasses {
+            if !self.superclasses.contains(&sup) {
+                self.superclasses.push(sup);
+            }
+        }
+        if rec.glob_deleteall {
+            self.globs.clear();
+            self.glob_deleteall = true;
+        }
+        for glob in rec.globs {
+            self.globs
+                .retain(|g| g.pattern != glob.pattern || g.case_sensitive != glob.case_sensitive);
+            self.globs.push(glob);
+        }
+        if rec.magic_deleteall {
+            self.magic.clear();
+            self.magic_deleteall = true;
+        }
+        self.magic.extend(rec.magic);
+        self.treemagic.extend(rec.treemagic);
+        for rx in rec.root_xml {
+            if !self.root_xml.contains(&rx) {
+                self.root_xml.push(rx);
+            }
+        }
+        self.extensions.extend(rec.extensions);
+    }
+}
+
+/// An element of a type definition that this crate does not interpret, such
+/// as a vendor extension or an element from a newer version of the spec.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExtensionElement {
+    /// The element name, including any namespace prefix.
+    pub name: String,
+    /// The element's attributes, in document order.
+    pub attributes: Vec<(String, String)>,
+}
+
+impl ExtensionElement {
+    /// Get the value of an attribute.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
 }
 
 /// A glob rule in the database.
@@ -31,3 +151,108 @@ pub struct GlobRule {
     // Whether this rule is case-sensitive.
     pub case_sensitive: bool,
 }
+
+/// A rule identifying an XML-based type by its document's root element.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RootXmlRule {
+    /// The root element's namespace URI (empty for no namespace).
+    pub namespace_uri: String,
+    /// The root element's local name.
+    pub local_name: String,
+}
+
+/// A magic (content sniffing) rule in the database.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MagicRule {
+    /// Rule priority (0–100, higher is checked first).
+    pub priority: u32,
+    /// Top-level matches; the rule matches if any of them match.
+    pub matches: Vec<MagicMatch>,
+}
+
+/// A single (possibly nested) match within a magic rule.
+///
+/// Values are stored as raw bytes, in the same form as the compiled `magic`
+/// file: multi-byte values are in big-endian order, and `word_size` is greater
+/// than 1 only for host-endian values that need swapping on little-endian
+/// machines.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MagicMatch {
+    /// First offset at which to look for the value.
+    pub offset: u32,
+    /// Number of offsets to try, starting at `offset` (at least 1).
+    pub range_length: u32,
+    /// Word size for host-endian values (1 for byte-order-independent values).
+    pub word_size: u32,
+    /// The bytes to match.
+    pub value: Vec<u8>,
+    /// Mask to apply to the data before comparison (same length as `value`).
+    pub mask: Option<Vec<u8>>,
+    /// Nested matches; if non-empty, at least one must also match.
+    pub children: Vec<MagicMatch>,
+}
+
+/// A tree magic rule, for detecting content types of directories and volumes.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TreeMagicRule {
+    /// Rule priority (0–100, higher is checked first).
+    pub priority: u32,
+    /// Top-level matches; the rule matches if any of them match.
+    pub matches: Vec<TreeMatch>,
+}
+
+/// A single (possibly nested) path match within a tree magic rule.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TreeMatch {
+    /// Path relative to the root of the tree.
+    pub path: String,
+    /// The required type of the object at the path, if any.
+    pub file_type: Option<TreeFileType>,
+    /// Whether the path must be matched case-sensitively.
+    pub match_case: bool,
+    /// Whether the object must be executable.
+    pub executable: bool,
+    /// Whether the object must be non-empty.
+    pub non_empty: bool,
+    /// The MIME type the object must have, if any.
+    pub mimetype: Option<String>,
+    /// Nested matches; if non-empty, at least one must also match.
+    pub children: Vec<TreeMatch>,
+}
+
+/// Type of filesystem object required by a [TreeMatch].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeFileType {
+    File,
+    Directory,
+    Link,
+}
+
+/// Build a record with case-insensitive globs (pattern and weight), for tests.
+#[cfg(test)]
+pub(crate) fn glob_record(name: &str, globs: &[(&str, i32)]) -> MimeTypeRecord {
+    MimeTypeRecord {
+        name: name.into(),
+        globs: globs
+            .iter()
+            .map(|(pattern, weight)| GlobRule {
+                pattern: pattern.to_string(),
+                weight: *weight,
+                case_sensitive: false,
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_record_defaults() {
+    let rec: MimeTypeRecord =
+        serde_json::from_str(r#"{"name": "text/x-hackem", "globs": []}"#).unwrap();
+    assert_eq!(rec.name, "text/x-hackem");
+    assert!(rec.localized_descriptions.is_empty());
+    assert!(!rec.glob_deleteall);
+    assert!(rec.treemagic.is_empty());
+    assert!(rec.extensions.is_empty());
+}
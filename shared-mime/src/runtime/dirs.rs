@@ -19,13 +19,14 @@ use log::*;
 
 /// Return the directories to check for MIME data, in processing order.
 pub fn xdg_mime_search_dirs() -> Vec<PathBuf> {
-    let mut paths = Vec::new();
-    xdg_data_dirs(&mut paths);
-    xdg_home_dir(&mut paths);
+    let mut paths = xdg_system_dirs();
+    paths.extend(xdg_user_dir());
     paths
 }
 
-fn xdg_data_dirs(paths: &mut Vec<PathBuf>) {
+/// Return the system MIME directories from `XDG_DATA_DIRS`.
+pub(crate) fn xdg_system_dirs() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
     if let Some(spec) = env::var_os("XDG_DATA_DIRS") {
         debug!("found $XDG_DATA_DIRS");
         for mut spath in env::split_paths(&spec) {
@@ -37,20 +38,23 @@ fn xdg_data_dirs(paths: &mut Vec<PathBuf>) {
         paths.push("/usr/share/mime".into());
         paths.push("/usr/local/share/mime".into());
     }
+    paths
 }
 
-fn xdg_home_dir(paths: &mut Vec<PathBuf>) {
+/// Return the user's MIME directory from `XDG_DATA_HOME` or `HOME`.
+pub(crate) fn xdg_user_dir() -> Option<PathBuf> {
     if let Some(spec) = env::var_os("XDG_DATA_HOME") {
         debug!("found $XDG_DATA_HOME");
-        paths.push(spec.into())
+        Some(spec.into())
     } else if let Some(path) = env::var_os("HOME") {
         debug!("constructing XDG_DATA_HOME from $HOME");
         let mut path = PathBuf::from(path);
         path.push(".local");
         path.push("share");
         path.push("mime");
-        paths.push(path);
+        Some(path)
     } else {
         warn!("no $HOME or $XDG_DATA_HOME");
+        None
     }
 }
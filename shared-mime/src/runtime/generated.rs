@@ -0,0 +1,665 @@
+//! Parse the files generated by `update-mime-info` in a MIME directory.
+//!
+//! Some installations only ship these generated files, and not the package XML
+//! they were built from.  The text files (`globs2`, `aliases`, `subclasses`,
+//! `icons`, `generic-icons` and `XMLnamespaces`) and the binary `magic` and
+//! `treemagic` files are parsed into [MimeTypeRecord]s.  Type descriptions
+//! are read from the per-type XML files (`media/subtype.xml`).
+//!
+//! Like xdgmime, malformed lines in the text files and per-type XML files
+//! that cannot be parsed are skipped with a warning.
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::ErrorKind;
+use std::iter::Peekable;
+use std::path::Path;
+
+use log::*;
+
+use crate::record::{
+    GlobRule, MagicMatch, MagicRule, MimeTypeRecord, RootXmlRule, TreeFileType, TreeMagicRule,
+    TreeMatch,
+};
+
+use super::{
+    mimeinfo::{LoadMode, PackageDiagnostic},
+    xdg_parse::parse_type_file,
+    XDGError,
+};
+
+const MAGIC_HEADER: &[u8] = b"MIME-Magic\0\n";
+const TREEMAGIC_HEADER: &[u8] = b"MIME-TreeMagic\0\n";
+
+/// Records being assembled from the generated files, keyed by type.
+type TypeRecords = BTreeMap<String, MimeTypeRecord>;
+
+/// Check whether a MIME directory contains generated files.
+pub fn has_generated_files<P: AsRef<Path>>(dir: P) -> bool {
+    let dir = dir.as_ref();
+    dir.join("globs2").exists() || dir.join("globs").exists() || dir.join("magic").exists()
+}
+
+/// Load the type records from the generated files in a MIME directory.
+pub fn load_generated_files<P: AsRef<Path>>(dir: P) -> Result<Vec<MimeTypeRecord>, XDGError> {
+    load_generated_files_with(dir.as_ref(), LoadMode::Strict, &mut Vec::new())
+}
+
+/// Load the type records from the generated files in a MIME directory,
+/// recording per-type XML files that cannot be parsed in [LoadMode::Lenient].
+pub(crate) fn load_generated_files_with(
+    dir: &Path,
+    mode: LoadMode,
+    diagnostics: &mut Vec<PackageDiagnostic>,
+) -> Result<Vec<MimeTypeRecord>, XDGError> {
+    let mut types = TypeRecords::new();
+
+    if let Some(text) = read_text(dir, "globs2")? {
+        parse_globs2(&text, &mut types);
+    } else if let Some(text) = read_text(dir, "globs")? {
+        parse_globs(&text, &mut types);
+    }
+    if let Some(text) = read_text(dir, "aliases")? {
+        for (alias, typ) in parse_pairs("aliases", &text, ' ') {
+            record(&mut types, typ).aliases.push(alias.into());
+        }
+    }
+    if let Some(text) = read_text(dir, "subclasses")? {
+        for (typ, parent) in parse_pairs("subclasses", &text, ' ') {
+            record(&mut types, typ).superclasses.push(parent.into());
+        }
+    }
+    if let Some(text) = read_text(dir, "icons")? {
+        for (typ, icon) in parse_pairs("icons", &text, ':') {
+            record(&mut types, typ).icon = Some(icon.into());
+        }
+    }
+    if let Some(text) = read_text(dir, "generic-icons")? {
+        for (typ, icon) in parse_pairs("generic-icons", &text, ':') {
+            record(&mut types, typ).generic_icon = Some(icon.into());
+        }
+    }
+    if let Some(text) = read_text(dir, "XMLnamespaces")? {
+        parse_xml_namespaces(&text, &mut types);
+    }
+    if let Some(data) = read_bytes(dir, "magic")? {
+        parse_magic(&data, &mut types)?;
+    }
+    if let Some(data) = read_bytes(dir, "treemagic")? {
+        parse_treemagic(&data, &mut types)?;
+    }
+
+    let mut records = types.into_values().collect();
+    add_type_files(dir, &mut records, mode, diagnostics)?;
+    Ok(records)
+}
+
+/// Add the descriptions from the per-type XML files (`media/subtype.xml`) in a
+/// MIME directory to its records.
+///
+/// The other generated files (and `mime.cache`) do not contain descriptions,
+/// so these files are the only place to find them.  Types with no other rules
+/// are added as new records.  Files that cannot be parsed are skipped, and
+/// recorded in `diagnostics` in [LoadMode::Lenient].
+pub(crate) fn add_type_files(
+    dir: &Path,
+    records: &mut Vec<MimeTypeRecord>,
+    mode: LoadMode,
+    diagnostics: &mut Vec<PackageDiagnostic>,
+) -> Result<(), XDGError> {
+    let mut index: HashMap<String, usize> = records
+        .iter()
+        .enumerate()
+        .map(|(i, r)| (r.name.clone(), i))
+        .collect();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let media = entry.file_name();
+        if media == "packages" || media.as_encoded_bytes().starts_with(b".") {
+            continue;
+        }
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        for file in fs::read_dir(entry.path())? {
+            let path = file?.path();
+            if path.extension().is_none_or(|e| e != "xml") {
+                continue;
+            }
+            debug!("reading type file {}", path.display());
+            // file names are lower-cased, so the type comes from the content
+            let desc = match parse_type_file(&path) {
+                Ok(typ) => MimeTypeRecord::from(typ),
+                Err((e, pos)) => {
+                    let diag = PackageDiagnostic::new(&path, &e, pos);
+                    warn!("skipping type file: {}", diag);
+                    if mode == LoadMode::Lenient {
+                        diagnostics.push(diag);
+                    }
+                    continue;
+                }
+            };
+            let i = *index.entry(desc.name.clone()).or_insert_with(|| {
+                records.push(MimeTypeRecord {
+                    name: desc.name.clone(),
+                    ..Default::default()
+                });
+                records.len() - 1
+            });
+            let rec = &mut records[i];
+            rec.description = desc.description;
+            rec.localized_descriptions = desc.localized_descriptions;
+            rec.acronym = desc.acronym;
+            rec.expanded_acronym = desc.expanded_acronym;
+        }
+    }
+    Ok(())
+}
+
+/// Add the rules from a MIME directory's `treemagic` file to its records.
+///
+/// `mime.cache` does not contain tree magic, so this is read alongside it.
+pub(crate) fn add_treemagic(dir: &Path, records: &mut Vec<MimeTypeRecord>) -> Result<(), XDGError> {
+    let Some(data) = read_bytes(dir, "treemagic")? else {
+        return Ok(());
+    };
+    let mut types = TypeRecords::new();
+    parse_treemagic(&data, &mut types)?;
+    for (name, tm) in types {
+        match records.iter_mut().find(|r| r.name == name) {
+            Some(rec) => rec.treemagic.extend(tm.treemagic),
+            None => records.push(tm),
+        }
+    }
+    Ok(())
+}
+
+fn read_bytes(dir: &Path, name: &str) -> Result<Option<Vec<u8>>, XDGError> {
+    let path = dir.join(name);
+    match fs::read(&path) {
+        Ok(data) => {
+            debug!("read {} bytes from {}", data.len(), path.display());
+            Ok(Some(data))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn read_text(dir: &Path, name: &str) -> Result<Option<String>, XDGError> {
+    read_bytes(dir, name)?
+        .map(|data| {
+            String::from_utf8(data).map_err(|e| format_error(name, format!("invalid UTF-8: {}", e)))
+        })
+        .transpose()
+}
+
+fn format_error<S: AsRef<str>>(file: &str, msg: S) -> XDGError {
+    XDGError::Format(format!("{}: {}", file, msg.as_ref()))
+}
+
+fn record<'a>(types: &'a mut TypeRecords, name: &str) -> &'a mut MimeTypeRecord {
+    types
+        .entry(name.to_string())
+        .or_insert_with(|| MimeTypeRecord {
+            name: name.into(),
+            ..Default::default()
+        })
+}
+
+/// Iterate over the non-empty, non-comment lines of a text file.
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+}
+
+/// Parse `globs2` lines (`weight:type:pattern[:flags]`).
+fn parse_globs2(text: &str, types: &mut TypeRecords) {
+    for line in lines(text) {
+        let mut parts = line.splitn(4, ':');
+        let (Some(weight), Some(typ), Some(pattern)) = (parts.next(), parts.next(), parts.next())
+        else {
+            warn!("globs2: skipping invalid line {}", line);
+            continue;
+        };
+        let weight = match weight.parse() {
+            Ok(w) => w,
+            Err(e) => {
+                warn!(
+                    "globs2: skipping line with invalid weight {}: {}",
+                    weight, e
+                );
+                continue;
+            }
+        };
+        let case_sensitive = parts
+            .next()
+            .is_some_and(|f| f.split(',').any(|f| f == "cs"));
+        let rec = record(types, typ);
+        if pattern == "__NOGLOBS__" {
+            rec.glob_deleteall = true;
+        } else {
+            rec.globs.push(GlobRule {
+                pattern: pattern.into(),
+                weight,
+                case_sensitive,
+            });
+        }
+    }
+    // case-sensitive globs are followed by a copy without flags for older
+    // readers, which would otherwise match regardless of case
+    for rec in types.values_mut() {
+        let cs: Vec<String> = rec
+            .globs
+            .iter()
+            .filter(|g| g.case_sensitive)
+            .map(|g| g.pattern.clone())
+            .collect();
+        rec.globs
+            .retain(|g| g.case_sensitive || !cs.contains(&g.pattern));
+    }
+}
+
+/// Parse the older `globs` lines (`type:pattern`).
+fn parse_globs(text: &str, types: &mut TypeRecords) {
+    for line in lines(text) {
+        let Some((typ, pattern)) = line.split_once(':') else {
+            warn!("globs: skipping invalid line {}", line);
+            continue;
+        };
+        let rec = record(types, typ);
+        if pattern == "__NOGLOBS__" {
+            rec.glob_deleteall = true;
+        } else {
+            rec.globs.push(GlobRule {
+                pattern: pattern.into(),
+                weight: 50,
+                case_sensitive: false,
+            });
+        }
+    }
+}
+
+/// Parse lines of two fields separated by `sep`, skipping invalid lines.
+fn parse_pairs<'a>(file: &str, text: &'a str, sep: char) -> Vec<(&'a str, &'a str)> {
+    lines(text)
+        .filter_map(|line| {
+            let pair = line.split_once(sep);
+            if pair.is_none() {
+                warn!("{}: skipping invalid line {}", file, line);
+            }
+            pair
+        })
+        .collect()
+}
+
+/// Parse `XMLnamespaces` lines (`namespaceURI localName type`).
+fn parse_xml_namespaces(text: &str, types: &mut TypeRecords) {
+    for line in lines(text) {
+        let fields: Vec<&str> = line.split(' ').collect();
+        let [ns, local, typ] = fields[..] else {
+            warn!("XMLnamespaces: skipping invalid line {}", line);
+            continue;
+        };
+        record(types, typ).root_xml.push(RootXmlRule {
+            namespace_uri: ns.into(),
+            local_name: local.into(),
+        });
+    }
+}
+
+/// Cursor over the bytes of a binary magic file.
+struct Cursor<'a> {
+    file: &'static str,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(file: &'static str, data: &'a [u8], header: &[u8]) -> Result<Cursor<'a>, XDGError> {
+        if !data.starts_with(header) {
+            return Err(format_error(file, "invalid header"));
+        }
+        Ok(Cursor {
+            file,
+            data,
+            pos: header.len(),
+        })
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn error<S: AsRef<str>>(&self, msg: S) -> XDGError {
+        format_error(self.file, format!("{} at byte {}", msg.as_ref(), self.pos))
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), XDGError> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {:?}", c as char)))
+        }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], XDGError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| self.error("unexpected end of file"))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn take_until(&mut self, c: u8) -> Result<&'a [u8], XDGError> {
+        let n = self.data[self.pos..]
+            .iter()
+            .position(|b| *b == c)
+            .ok_or_else(|| self.error(format!("missing {:?}", c as char)))?;
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n + 1;
+        Ok(bytes)
+    }
+
+    /// Read an optional decimal number.
+    fn number(&mut self) -> Result<Option<u32>, XDGError> {
+        let n = self.data[self.pos..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        if n == 0 {
+            return Ok(None);
+        }
+        let digits = std::str::from_utf8(self.take(n)?).expect("digits are ASCII");
+        digits
+            .parse()
+            .map(Some)
+            .map_err(|e| self.error(format!("invalid number: {}", e)))
+    }
+
+    /// Read a section header (`[priority:type]\n`).
+    fn section(&mut self) -> Result<(u32, String), XDGError> {
+        self.expect(b'[')?;
+        let head = self.take_until(b']')?;
+        self.expect(b'\n')?;
+        let head = String::from_utf8_lossy(head);
+        let Some((prio, typ)) = head.split_once(':') else {
+            return Err(self.error(format!("invalid section {}", head)));
+        };
+        let prio = prio
+            .parse()
+            .map_err(|e| self.error(format!("invalid priority {}: {}", prio, e)))?;
+        Ok((prio, typ.to_string()))
+    }
+}
+
+/// Parse the binary `magic` file.
+fn parse_magic(data: &[u8], types: &mut TypeRecords) -> Result<(), XDGError> {
+    let mut cur = Cursor::new("magic", data, MAGIC_HEADER)?;
+    while !cur.at_end() {
+        let (priority, typ) = cur.section()?;
+        let mut matches = Vec::new();
+        while !cur.at_end() && cur.peek() != Some(b'[') {
+            if let Some(m) = parse_matchlet(&mut cur)? {
+                matches.push(m);
+            }
+        }
+        let rec = record(types, &typ);
+        let mut iter = matches.into_iter().peekable();
+        let matches = build_tree(&mut iter, 0);
+        if matches.iter().any(|m| m.value == b"__NOMAGIC__") {
+            rec.magic_deleteall = true;
+        } else {
+            rec.magic.push(MagicRule { priority, matches });
+        }
+    }
+    Ok(())
+}
+
+/// Parse a matchlet line, returning its indent and match.  Lines with unknown
+/// fields are skipped.
+fn parse_matchlet(cur: &mut Cursor<'_>) -> Result<Option<(u32, MagicMatch)>, XDGError> {
+    let indent = cur.number()?.unwrap_or(0);
+    cur.expect(b'>')?;
+    let offset = cur.number()?.ok_or_else(|| cur.error("missing offset"))?;
+    cur.expect(b'=')?;
+    let len = cur.take(2)?;
+    let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+    let value = cur.take(len)?.to_vec();
+    let mut m = MagicMatch {
+        offset,
+        range_length: 1,
+        word_size: 1,
+        value,
+        mask: None,
+        children: Vec::new(),
+    };
+    loop {
+        match cur.peek() {
+            Some(b'&') => {
+                cur.pos += 1;
+                m.mask = Some(cur.take(len)?.to_vec());
+            }
+            Some(b'~') => {
+                cur.pos += 1;
+                m.word_size = cur
+                    .number()?
+                    .ok_or_else(|| cur.error("missing word size"))?;
+            }
+            Some(b'+') => {
+                cur.pos += 1;
+                m.range_length = cur.number()?.ok_or_else(|| cur.error("missing range"))?;
+            }
+            Some(b'\n') => {
+                cur.pos += 1;
+                return Ok(Some((indent, m)));
+            }
+            _ => {
+                warn!("magic: skipping line with unknown fields");
+                cur.take_until(b'\n')?;
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Parse the binary `treemagic` file.
+fn parse_treemagic(data: &[u8], types: &mut TypeRecords) -> Result<(), XDGError> {
+    let mut cur = Cursor::new("treemagic", data, TREEMAGIC_HEADER)?;
+    while !cur.at_end() {
+        let (priority, typ) = cur.section()?;
+        let mut matches = Vec::new();
+        while !cur.at_end() && cur.peek() != Some(b'[') {
+            matches.push(parse_treematch(&mut cur)?);
+        }
+        let mut iter = matches.into_iter().peekable();
+        let matches = build_tree(&mut iter, 0);
+        record(types, &typ)
+            .treemagic
+            .push(TreeMagicRule { priority, matches });
+    }
+    Ok(())
+}
+
+/// Parse a treematch line (`[indent]>"path"=type[,flags]`).
+fn parse_treematch(cur: &mut Cursor<'_>) -> Result<(u32, TreeMatch), XDGError> {
+    let indent = cur.number()?.unwrap_or(0);
+    cur.expect(b'>')?;
+    cur.expect(b'"')?;
+    let path = String::from_utf8_lossy(cur.take_until(b'"')?).to_string();
+    cur.expect(b'=')?;
+    let spec = String::from_utf8_lossy(cur.take_until(b'\n')?).to_string();
+    let mut fields = spec.split(',');
+    let file_type = match fields.next() {
+        Some("file") => Some(TreeFileType::File),
+        Some("directory") => Some(TreeFileType::Directory),
+        Some("link") => Some(TreeFileType::Link),
+        _ => None,
+    };
+    let mut tm = TreeMatch {
+        path,
+        file_type,
+        match_case: false,
+        executable: false,
+        non_empty: false,
+        mimetype: None,
+        children: Vec::new(),
+    };
+    for field in fields {
+        match field {
+            "executable" => tm.executable = true,
+            "match-case" => tm.match_case = true,
+            "non-empty" => tm.non_empty = true,
+            mt if mt.contains('/') => tm.mimetype = Some(mt.into()),
+            f => warn!("treemagic: unknown flag {}", f),
+        }
+    }
+    Ok((indent, tm))
+}
+
+/// Matches that can be nested by indent level.
+trait Nested {
+    fn children(&mut self) -> &mut Vec<Self>
+    where
+        Self: Sized;
+}
+
+impl Nested for MagicMatch {
+    fn children(&mut self) -> &mut Vec<Self> {
+        &mut self.children
+    }
+}
+
+impl Nested for TreeMatch {
+    fn children(&mut self) -> &mut Vec<Self> {
+        &mut self.children
+    }
+}
+
+/// Assemble a flat list of indented matches into a tree.
+fn build_tree<T, I>(items: &mut Peekable<I>, indent: u32) -> Vec<T>
+where
+    T: Nested,
+    I: Iterator<Item = (u32, T)>,
+{
+    let mut out = Vec::new();
+    while let Some((i, _)) = items.peek() {
+        if *i < indent {
+            break;
+        }
+        let (i, mut m) = items.next().expect("peeked item");
+        *m.children() = build_tree(items, i + 1);
+        out.push(m);
+    }
+    out
+}
+
+#[test]
+fn test_parse_globs2() {
+    let mut types = TypeRecords::new();
+    let text =
+        "# comment\n80:text/html:*.html\n50:text/x-c++src:*.C:cs\n50:text/x-c++src:*.C\n50:text/x-foo:__NOGLOBS__\nbogus\nx:text/x-bar:*.bar\n";
+    parse_globs2(text, &mut types);
+    let html = &types["text/html"];
+    assert_eq!(html.globs[0].pattern, "*.html");
+    assert_eq!(html.globs[0].weight, 80);
+    assert!(!html.globs[0].case_sensitive);
+    assert_eq!(types["text/x-c++src"].globs.len(), 1);
+    assert!(types["text/x-c++src"].globs[0].case_sensitive);
+    assert!(types["text/x-foo"].glob_deleteall);
+    assert!(types["text/x-foo"].globs.is_empty());
+    assert!(!types.contains_key("text/x-bar"));
+}
+
+#[test]
+fn test_parse_pairs() {
+    let pairs = parse_pairs("aliases", "application/x-pdf application/pdf\n", ' ');
+    assert_eq!(pairs, vec![("application/x-pdf", "application/pdf")]);
+    let pairs = parse_pairs("icons", "text/plain\ntext/x-c:text-x-generic\n", ':');
+    assert_eq!(pairs, vec![("text/x-c", "text-x-generic")]);
+}
+
+#[test]
+fn test_parse_namespaces() {
+    let mut types = TypeRecords::new();
+    parse_xml_namespaces("http://www.w3.org/2000/svg svg image/svg+xml\n", &mut types);
+    let rx = &types["image/svg+xml"].root_xml[0];
+    assert_eq!(rx.namespace_uri, "http://www.w3.org/2000/svg");
+    assert_eq!(rx.local_name, "svg");
+}
+
+#[test]
+fn test_parse_magic() {
+    let mut data = MAGIC_HEADER.to_vec();
+    data.extend_from_slice(b"[50:audio/x-wav]\n>0=\0\x04RIFF\n1>8=\0\x04WAVE\n");
+    data.extend_from_slice(b"[60:application/x-tar]\n>257=\0\x05ustar&\0\xff\xff\xff\xff~1+2\n");
+    data.extend_from_slice(b"[50:application/x-old]\n>0=\0\x0b__NOMAGIC__\n");
+    let mut types = TypeRecords::new();
+    parse_magic(&data, &mut types).unwrap();
+
+    let wav = &types["audio/x-wav"].magic[0];
+    assert_eq!(wav.priority, 50);
+    assert_eq!(wav.matches.len(), 1);
+    assert_eq!(wav.matches[0].value, b"RIFF");
+    assert_eq!(wav.matches[0].children[0].offset, 8);
+    assert_eq!(wav.matches[0].children[0].value, b"WAVE");
+
+    let tar = &types["application/x-tar"].magic[0];
+    assert_eq!(tar.matches[0].offset, 257);
+    assert_eq!(tar.matches[0].range_length, 2);
+    assert_eq!(tar.matches[0].mask, Some(b"\0\xff\xff\xff\xff".to_vec()));
+
+    assert!(types["application/x-old"].magic_deleteall);
+}
+
+#[test]
+fn test_parse_treemagic() {
+    let mut data = TREEMAGIC_HEADER.to_vec();
+    data.extend_from_slice(b"[50:x-content/image-dcf]\n>\"dcim\"=directory,non-empty\n");
+    data.extend_from_slice(b"[20:x-content/unix-software]\n>\"autorun\"=file,match-case\n");
+    data.extend_from_slice(b"1>\"autorun\"=file,executable,application/x-shellscript\n");
+    let mut types = TypeRecords::new();
+    parse_treemagic(&data, &mut types).unwrap();
+
+    let dcf = &types["x-content/image-dcf"].treemagic[0];
+    assert_eq!(dcf.matches[0].path, "dcim");
+    assert_eq!(dcf.matches[0].file_type, Some(TreeFileType::Directory));
+    assert!(dcf.matches[0].non_empty);
+
+    let sw = &types["x-content/unix-software"].treemagic[0];
+    assert_eq!(sw.priority, 20);
+    assert!(sw.matches[0].match_case);
+    let child = &sw.matches[0].children[0];
+    assert!(child.executable);
+    assert_eq!(child.mimetype.as_deref(), Some("application/x-shellscript"));
+}
+
+#[test]
+fn test_load_generated_files() {
+    // update-mime-database output for the fixture package
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/hackem");
+    let records = load_generated_files(&dir).unwrap();
+    let types: TypeRecords = records.into_iter().map(|r| (r.name.clone(), r)).collect();
+    assert_eq!(types.len(), 4);
+
+    let hkm = &types["application/x-hackem"];
+    assert_eq!(hkm.description.as_deref(), Some("Hackem archive"));
+    assert_eq!(hkm.localized_descriptions["de"], "Hackem-Archiv");
+    assert_eq!(hkm.acronym.as_deref(), Some("HKM"));
+    assert_eq!(hkm.expanded_acronym.as_deref(), Some("Hackem Muche"));
+    assert!(hkm.glob_deleteall);
+    assert!(hkm.magic_deleteall);
+    assert_eq!(hkm.globs.len(), 4);
+
+    let note = &types["text/x-rogue-note"];
+    assert_eq!(note.description.as_deref(), Some("Rogue note"));
+    assert!(note.globs.is_empty());
+
+    let game = &types["x-content/x-rogue-game"];
+    assert_eq!(game.treemagic[0].matches[0].children.len(), 1);
+}
@@ -0,0 +1,252 @@
+//! Configurable loading of MIME databases.
+use std::path::PathBuf;
+
+use log::*;
+
+use crate::{mimedb::StaticIndex, record::MimeTypeRecord, LoadError, MimeDB};
+
+use super::{
+    dirs::{xdg_system_dirs, xdg_user_dir},
+    mimeinfo::{load_package, load_xdg_mime_dir, LoadMode, PackageDiagnostic, SharedMimeInfo},
+    XDGError,
+};
+
+/// Builder for loading a MIME database from a chosen set of sources.
+///
+/// Sources are layered in the order they are added, with later layers taking
+/// precedence over earlier ones (as later XDG directories do).  The XDG
+/// directories are only used if [MimeDbLoader::xdg_dirs] is called, and the
+/// environment is only consulted for directories that are not set explicitly.
+///
+/// ```no_run
+/// # use shared_mime::runtime::MimeDbLoader;
+/// let db = MimeDbLoader::new()
+///     .data_dirs(["/usr/share"])
+///     .user_dir(false)
+///     .xdg_dirs()
+///     .package("local-types.xml")
+///     .load()?;
+/// # Ok::<(), shared_mime::LoadError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct MimeDbLoader {
+    layers: Vec<Layer>,
+    data_dirs: Option<Vec<PathBuf>>,
+    data_home: Option<PathBuf>,
+    user_dir: bool,
+    mode: LoadMode,
+}
+
+#[derive(Debug, Clone)]
+enum Layer {
+    Xdg,
+    Dir(PathBuf),
+    Package(PathBuf),
+    Records(String, Vec<MimeTypeRecord>),
+    Precompiled(&'static StaticIndex),
+}
+
+impl Default for MimeDbLoader {
+    fn default() -> Self {
+        MimeDbLoader {
+            layers: Vec::new(),
+            data_dirs: None,
+            data_home: None,
+            user_dir: true,
+            mode: LoadMode::Strict,
+        }
+    }
+}
+
+impl MimeDbLoader {
+    /// Create a loader with no sources.
+    pub fn new() -> MimeDbLoader {
+        MimeDbLoader::default()
+    }
+
+    /// Set the data directories (such as `/usr/share`) to use instead of
+    /// `XDG_DATA_DIRS`.
+    ///
+    /// Their `mime` subdirectories are loaded in the order given, so later
+    /// directories take precedence.
+    pub fn data_dirs<I, P>(mut self, dirs: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.data_dirs = Some(dirs.into_iter().map(|d| d.into()).collect());
+        self
+    }
+
+    /// Set the user data directory (such as `~/.local/share`) to use instead of
+    /// `XDG_DATA_HOME`.
+    pub fn data_home<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.data_home = Some(dir.into());
+        self
+    }
+
+    /// Set whether to load the user's MIME directory after the system
+    /// directories (the default is `true`).
+    pub fn user_dir(mut self, include: bool) -> Self {
+        self.user_dir = include;
+        self
+    }
+
+    /// Set how to handle package files that cannot be parsed (the default is
+    /// [LoadMode::Strict]).
+    pub fn mode(mut self, mode: LoadMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Add a layer for the XDG MIME directories.
+    pub fn xdg_dirs(mut self) -> Self {
+        self.layers.push(Layer::Xdg);
+        self
+    }
+
+    /// Add a layer for a MIME directory (containing `packages/` or the
+    /// generated files).
+    pub fn dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.layers.push(Layer::Dir(dir.into()));
+        self
+    }
+
+    /// Add a layer for a single package XML file.
+    pub fn package<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.layers.push(Layer::Package(path.into()));
+        self
+    }
+
+    /// Add a layer of type records, reported in query results as coming from
+    /// `origin`.
+    pub fn records<S: Into<String>>(mut self, origin: S, records: Vec<MimeTypeRecord>) -> Self {
+        self.layers.push(Layer::Records(origin.into(), records));
+        self
+    }
+
+    /// Add a layer for a precompiled database.
+    ///
+    /// If this is the first layer, the database is used directly.  Otherwise,
+    /// its rules are copied in with [MimeDB::add_static].
+    pub fn precompiled(mut self, index: &'static StaticIndex) -> Self {
+        self.layers.push(Layer::Precompiled(index));
+        self
+    }
+
+    /// Get the MIME directories used by [MimeDbLoader::xdg_dirs], in
+    /// processing order.
+    pub fn search_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = match &self.data_dirs {
+            Some(dirs) => dirs.iter().map(|d| d.join("mime")).collect(),
+            None => xdg_system_dirs(),
+        };
+        if self.user_dir {
+            match &self.data_home {
+                Some(home) => dirs.push(home.join("mime")),
+                None => dirs.extend(xdg_user_dir()),
+            }
+        }
+        dirs
+    }
+
+    /// Load the database.
+    pub fn load(self) -> Result<MimeDB, LoadError> {
+        Ok(self.load_with_diagnostics()?.0)
+    }
+
+    /// Load the database, also returning the package files that were skipped
+    /// in [LoadMode::Lenient].
+    pub fn load_with_diagnostics(self) -> Result<(MimeDB, Vec<PackageDiagnostic>), LoadError> {
+        let mode = self.mode;
+        let mut diagnostics = Vec::new();
+        let search_dirs = self.search_dirs();
+        let mut layers = self.layers.into_iter().peekable();
+        let mut db = match layers.next_if(|l| matches!(l, Layer::Precompiled(_))) {
+            Some(Layer::Precompiled(index)) => MimeDB::from_static(index),
+            _ => MimeDB::new(),
+        };
+        for layer in layers {
+            match layer {
+                Layer::Xdg => {
+                    let mut info = SharedMimeInfo::default();
+                    for dir in &search_dirs {
+                        info.directories
+                            .extend(load_xdg_mime_dir(dir, mode, &mut diagnostics)?);
+                    }
+                    db.add_shared_mime_info(info);
+                }
+                Layer::Dir(path) => {
+                    let Some(dir) = load_xdg_mime_dir(&path, mode, &mut diagnostics)? else {
+                        return Err(XDGError::Layout(format!(
+                            "{}: not a MIME directory",
+                            path.display()
+                        ))
+                        .into());
+                    };
+                    db.add_shared_mime_info(SharedMimeInfo {
+                        directories: vec![dir],
+                        ..Default::default()
+                    });
+                }
+                Layer::Package(path) => {
+                    debug!("loading package {}", path.display());
+                    if let Some(pkg) = load_package(&path, mode, &mut diagnostics)? {
+                        db.add_records_from(path.display().to_string(), pkg.into_records());
+                    }
+                }
+                Layer::Records(origin, records) => db.add_records_from(origin, records),
+                Layer::Precompiled(index) => db.add_static(index),
+            }
+        }
+        debug!(
+            "loaded MIME database with {} types and {} globs",
+            db.type_count(),
+            db.glob_count()
+        );
+        Ok((db, diagnostics))
+    }
+}
+
+#[test]
+fn test_loader_layers() {
+    use std::fs;
+
+    use crate::record::glob_record;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    fs::create_dir_all(dir.join("data/mime/packages")).unwrap();
+    fs::write(
+        dir.join("data/mime/packages/hackem.xml"),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<mime-info xmlns="http://www.freedesktop.org/standards/shared-mime-info">
+  <mime-type type="text/x-hackem">
+    <glob pattern="*.hkm"/>
+  </mime-type>
+</mime-info>
+"#,
+    )
+    .unwrap();
+
+    let loader = MimeDbLoader::new()
+        .data_dirs([dir.join("data")])
+        .user_dir(false)
+        .xdg_dirs();
+    assert_eq!(loader.search_dirs(), vec![dir.join("data/mime")]);
+    let db = loader
+        .clone()
+        .records(
+            "override",
+            vec![glob_record("text/x-muche", &[("*.hkm", 50)])],
+        )
+        .load()
+        .unwrap();
+    let missing = MimeDbLoader::new().dir(dir.join("missing")).load();
+
+    assert_eq!(db.type_count(), 2);
+    assert_eq!(db.query_filename("a.hkm").best(), Some("text/x-muche"));
+    let ans = db.query_filename("a.hkm").into_owned();
+    assert_eq!(ans.candidates[0].origin.as_deref(), Some("override"));
+    assert!(missing.is_err());
+}
@@ -0,0 +1,757 @@
+//! Read and write the binary `mime.cache` file produced by `update-mime-info`.
+//!
+//! The cache format is described in the [shared MIME info spec][SMI].  It holds
+//! the aliases, parents, globs, magic, XML namespaces and icons for every type
+//! in a MIME directory, but not descriptions or tree magic.
+//!
+//! [SMI]:
+//!     https://specifications.freedesktop.org/shared-mime-info-spec/shared-mime-info-spec-latest.html
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::path::Path;
+
+use log::*;
+use memmap2::Mmap;
+
+use crate::record::{GlobRule, MagicMatch, MagicRule, MimeTypeRecord, RootXmlRule};
+
+use super::XDGError;
+
+const MATCH_SIZE: usize = 16;
+const MATCHLET_SIZE: usize = 32;
+const NODE_SIZE: usize = 12;
+const HEADER_SIZE: usize = 40;
+const CACHE_VERSION: u32 = 0x0001_0002;
+
+/// A memory-mapped `mime.cache` file.
+pub struct MimeCache {
+    data: Mmap,
+}
+
+/// Offsets of the lists in the cache header.
+struct Header {
+    aliases: usize,
+    parents: usize,
+    literals: usize,
+    suffixes: usize,
+    globs: usize,
+    magic: usize,
+    namespaces: usize,
+    icons: usize,
+    generic_icons: usize,
+}
+
+impl MimeCache {
+    /// Open a cache file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<MimeCache, XDGError> {
+        let file = File::open(path.as_ref())?;
+        // SAFETY: the cache is only read, and update-mime-info replaces it
+        // atomically (by rename) rather than modifying it in place.
+        let data = unsafe { Mmap::map(&file)? };
+        let cache = MimeCache { data };
+        let major = cache.u16_at(0)?;
+        let minor = cache.u16_at(2)?;
+        if major != 1 || minor < 1 {
+            return Err(XDGError::Cache(format!(
+                "unsupported cache version {}.{}",
+                major, minor
+            )));
+        }
+        Ok(cache)
+    }
+
+    /// Read all type records from the cache.
+    ///
+    /// Glob and magic deletions (the `__NOGLOBS__` and `__NOMAGIC__` entries)
+    /// are read as [MimeTypeRecord::glob_deleteall] and
+    /// [MimeTypeRecord::magic_deleteall].  The cache has no descriptions; they
+    /// are in the per-type XML files next to it.
+    pub fn records(&self) -> Result<Vec<MimeTypeRecord>, XDGError> {
+        let hdr = self.header()?;
+        let mut types = BTreeMap::new();
+
+        self.read_pairs(hdr.aliases, |alias, typ| {
+            record(&mut types, typ).aliases.push(alias.into())
+        })?;
+        for i in 0..self.count(hdr.parents)? {
+            let entry = hdr.parents + 4 + i * 8;
+            let typ = self.str_at(self.u32_at(entry)? as usize)?;
+            let plist = self.u32_at(entry + 4)? as usize;
+            let rec = record(&mut types, typ);
+            for j in 0..self.count(plist)? {
+                let parent = self.str_at(self.u32_at(plist + 4 + j * 4)? as usize)?;
+                rec.superclasses.push(parent.into());
+            }
+        }
+        for list in [hdr.literals, hdr.globs] {
+            for i in 0..self.count(list)? {
+                let entry = list + 4 + i * 12;
+                let pattern = self.str_at(self.u32_at(entry)? as usize)?;
+                let typ = self.str_at(self.u32_at(entry + 4)? as usize)?;
+                let rec = record(&mut types, typ);
+                if pattern == "__NOGLOBS__" {
+                    rec.glob_deleteall = true;
+                } else {
+                    rec.globs
+                        .push(glob_rule(pattern.into(), self.u32_at(entry + 8)?));
+                }
+            }
+        }
+        let n_roots = self.count(hdr.suffixes)?;
+        let first = self.u32_at(hdr.suffixes + 4)? as usize;
+        self.read_suffix_nodes(first, n_roots, &mut Vec::new(), &mut types)?;
+        self.read_magic(hdr.magic, &mut types)?;
+        for i in 0..self.count(hdr.namespaces)? {
+            let entry = hdr.namespaces + 4 + i * 12;
+            let ns = self.str_at(self.u32_at(entry)? as usize)?;
+            let local = self.str_at(self.u32_at(entry + 4)? as usize)?;
+            let typ = self.str_at(self.u32_at(entry + 8)? as usize)?;
+            record(&mut types, typ).root_xml.push(RootXmlRule {
+                namespace_uri: ns.into(),
+                local_name: local.into(),
+            });
+        }
+        self.read_pairs(hdr.icons, |typ, icon| {
+            record(&mut types, typ).icon = Some(icon.into())
+        })?;
+        self.read_pairs(hdr.generic_icons, |typ, icon| {
+            record(&mut types, typ).generic_icon = Some(icon.into())
+        })?;
+
+        Ok(types.into_values().collect())
+    }
+
+    fn header(&self) -> Result<Header, XDGError> {
+        let off = |i: usize| self.u32_at(4 + i * 4).map(|o| o as usize);
+        Ok(Header {
+            aliases: off(0)?,
+            parents: off(1)?,
+            literals: off(2)?,
+            suffixes: off(3)?,
+            globs: off(4)?,
+            magic: off(5)?,
+            namespaces: off(6)?,
+            icons: off(7)?,
+            generic_icons: off(8)?,
+        })
+    }
+
+    /// Read a list of string pairs.
+    fn read_pairs<F: FnMut(&str, &str)>(&self, list: usize, mut f: F) -> Result<(), XDGError> {
+        for i in 0..self.count(list)? {
+            let entry = list + 4 + i * 8;
+            let a = self.str_at(self.u32_at(entry)? as usize)?;
+            let b = self.str_at(self.u32_at(entry + 4)? as usize)?;
+            f(a, b);
+        }
+        Ok(())
+    }
+
+    /// Walk the reverse suffix tree, collecting the suffix globs at its leaves.
+    fn read_suffix_nodes(
+        &self,
+        first: usize,
+        n: usize,
+        suffix: &mut Vec<char>,
+        types: &mut BTreeMap<String, MimeTypeRecord>,
+    ) -> Result<(), XDGError> {
+        for i in 0..n {
+            let node = first + i * NODE_SIZE;
+            let c = self.u32_at(node)?;
+            if c == 0 {
+                let typ = self.str_at(self.u32_at(node + 4)? as usize)?;
+                let pattern: String = std::iter::once('*')
+                    .chain(suffix.iter().rev().copied())
+                    .collect();
+                let glob = glob_rule(pattern, self.u32_at(node + 8)?);
+                record(types, typ).globs.push(glob);
+            } else {
+                let c = char::from_u32(c)
+                    .ok_or_else(|| XDGError::Cache(format!("invalid character {:x}", c)))?;
+                let n_children = self.u32_at(node + 4)? as usize;
+                let first_child = self.u32_at(node + 8)? as usize;
+                suffix.push(c);
+                self.read_suffix_nodes(first_child, n_children, suffix, types)?;
+                suffix.pop();
+            }
+        }
+        Ok(())
+    }
+
+    fn read_magic(
+        &self,
+        list: usize,
+        types: &mut BTreeMap<String, MimeTypeRecord>,
+    ) -> Result<(), XDGError> {
+        let n = self.count(list)?;
+        let first = self.u32_at(list + 8)? as usize;
+        for i in 0..n {
+            let entry = first + i * MATCH_SIZE;
+            let priority = self.u32_at(entry)?;
+            let typ = self.str_at(self.u32_at(entry + 4)? as usize)?;
+            let n_matchlets = self.u32_at(entry + 8)? as usize;
+            let matchlets = self.u32_at(entry + 12)? as usize;
+            let matches = self.read_matchlets(matchlets, n_matchlets)?;
+            let rec = record(types, typ);
+            if matches.iter().any(|m| m.value == b"__NOMAGIC__") {
+                rec.magic_deleteall = true;
+            } else {
+                rec.magic.push(MagicRule { priority, matches });
+            }
+        }
+        Ok(())
+    }
+
+    fn read_matchlets(&self, first: usize, n: usize) -> Result<Vec<MagicMatch>, XDGError> {
+        let mut matches = Vec::with_capacity(n);
+        for i in 0..n {
+            let m = first + i * MATCHLET_SIZE;
+            let offset = self.u32_at(m)?;
+            let range_length = self.u32_at(m + 4)?;
+            let word_size = self.u32_at(m + 8)?;
+            let len = self.u32_at(m + 12)? as usize;
+            let value = self.bytes_at(self.u32_at(m + 16)? as usize, len)?.to_vec();
+            let mask_off = self.u32_at(m + 20)? as usize;
+            let mask = if mask_off > 0 {
+                Some(self.bytes_at(mask_off, len)?.to_vec())
+            } else {
+                None
+            };
+            let n_children = self.u32_at(m + 24)? as usize;
+            let children = self.read_matchlets(self.u32_at(m + 28)? as usize, n_children)?;
+            matches.push(MagicMatch {
+                offset,
+                range_length,
+                word_size,
+                value,
+                mask,
+                children,
+            });
+        }
+        Ok(matches)
+    }
+
+    fn count(&self, list: usize) -> Result<usize, XDGError> {
+        self.u32_at(list).map(|n| n as usize)
+    }
+
+    fn bytes_at(&self, off: usize, len: usize) -> Result<&[u8], XDGError> {
+        self.data
+            .get(off..off + len)
+            .ok_or_else(|| XDGError::Cache(format!("offset {} out of bounds", off)))
+    }
+
+    fn u16_at(&self, off: usize) -> Result<u16, XDGError> {
+        let bytes = self.bytes_at(off, 2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32_at(&self, off: usize) -> Result<u32, XDGError> {
+        let bytes = self.bytes_at(off, 4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn str_at(&self, off: usize) -> Result<&str, XDGError> {
+        let rest = self
+            .data
+            .get(off..)
+            .ok_or_else(|| XDGError::Cache(format!("offset {} out of bounds", off)))?;
+        let len = rest
+            .iter()
+            .position(|c| *c == 0)
+            .ok_or_else(|| XDGError::Cache(format!("unterminated string at {}", off)))?;
+        std::str::from_utf8(&rest[..len])
+            .map_err(|e| XDGError::Cache(format!("invalid string at {}: {}", off, e)))
+    }
+}
+
+fn record<'a>(
+    types: &'a mut BTreeMap<String, MimeTypeRecord>,
+    name: &str,
+) -> &'a mut MimeTypeRecord {
+    types
+        .entry(name.to_string())
+        .or_insert_with(|| MimeTypeRecord {
+            name: name.into(),
+            ..Default::default()
+        })
+}
+
+/// Decode a glob weight field (weight in the low 8 bits, then a case-sensitive flag).
+fn glob_rule(pattern: String, weight: u32) -> GlobRule {
+    GlobRule {
+        pattern,
+        weight: (weight & 0xff) as i32,
+        case_sensitive: weight & 0x100 != 0,
+    }
+}
+
+/// Build the contents of a `mime.cache` file for a directory's type records.
+///
+/// Globs that are not case-sensitive are stored in lower case, as readers
+/// look them up with the lower-cased file name.  Glob and magic deletions are
+/// written as `__NOGLOBS__` and `__NOMAGIC__` entries, as `update-mime-info`
+/// writes them.
+pub fn write_cache(records: &[MimeTypeRecord]) -> Vec<u8> {
+    let nomagic = vec![MagicMatch {
+        offset: 0,
+        range_length: 1,
+        word_size: 1,
+        value: b"__NOMAGIC__".to_vec(),
+        mask: None,
+        children: Vec::new(),
+    }];
+    let mut aliases = Vec::new();
+    let mut parents = Vec::new();
+    let mut literals = Vec::new();
+    let mut suffixes = SuffixNode::default();
+    let mut globs = Vec::new();
+    let mut magic = Vec::new();
+    let mut namespaces = Vec::new();
+    let mut icons = Vec::new();
+    let mut generic_icons = Vec::new();
+
+    for rec in records {
+        let typ = rec.name.as_str();
+        for alias in rec.aliases.iter() {
+            aliases.push((alias.as_str(), typ));
+        }
+        if !rec.superclasses.is_empty() {
+            parents.push((typ, &rec.superclasses));
+        }
+        if rec.glob_deleteall {
+            literals.push(("__NOGLOBS__".into(), typ, 0));
+        }
+        for glob in rec.globs.iter() {
+            let pattern = if glob.case_sensitive {
+                glob.pattern.clone()
+            } else {
+                glob.pattern.to_lowercase()
+            };
+            let weight = (glob.weight as u32 & 0xff) | if glob.case_sensitive { 0x100 } else { 0 };
+            if is_literal(&pattern) {
+                literals.push((pattern, typ, weight));
+            } else if let Some(suffix) = simple_suffix(&pattern) {
+                suffixes.add(suffix, typ, weight);
+            } else {
+                globs.push((pattern, typ, weight));
+            }
+        }
+        if rec.magic_deleteall {
+            magic.push((0, typ, &nomagic));
+        }
+        for rule in rec.magic.iter() {
+            magic.push((rule.priority, typ, &rule.matches));
+        }
+        for rx in rec.root_xml.iter() {
+            namespaces.push((rx.namespace_uri.as_str(), rx.local_name.as_str(), typ));
+        }
+        if let Some(icon) = &rec.icon {
+            icons.push((typ, icon.as_str()));
+        }
+        if let Some(icon) = &rec.generic_icon {
+            generic_icons.push((typ, icon.as_str()));
+        }
+    }
+    // readers binary-search these lists
+    aliases.sort();
+    parents.sort();
+    literals.sort();
+    namespaces.sort();
+    icons.sort();
+    generic_icons.sort();
+    globs.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| (&a.0, a.1).cmp(&(&b.0, b.1))));
+    magic.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+
+    let mut w = CacheWriter::default();
+    w.reserve(HEADER_SIZE);
+    w.set(0, CACHE_VERSION);
+
+    let list = w.reserve(4 + aliases.len() * 8);
+    w.set(4, list as u32);
+    w.set(list, aliases.len() as u32);
+    for (i, (alias, typ)) in aliases.iter().enumerate() {
+        let entry = list + 4 + i * 8;
+        let alias = w.string(alias);
+        let typ = w.string(typ);
+        w.set(entry, alias);
+        w.set(entry + 4, typ);
+    }
+
+    let list = w.reserve(4 + parents.len() * 8);
+    w.set(8, list as u32);
+    w.set(list, parents.len() as u32);
+    for (i, (typ, supers)) in parents.iter().enumerate() {
+        let entry = list + 4 + i * 8;
+        let typ = w.string(typ);
+        w.set(entry, typ);
+        let plist = w.reserve(4 + supers.len() * 4);
+        w.set(entry + 4, plist as u32);
+        w.set(plist, supers.len() as u32);
+        for (j, parent) in supers.iter().enumerate() {
+            let parent = w.string(parent);
+            w.set(plist + 4 + j * 4, parent);
+        }
+    }
+
+    let list = w.glob_list(&literals);
+    w.set(12, list);
+
+    let list = w.reserve(8);
+    w.set(16, list as u32);
+    let (n, first) = w.suffix_nodes(&suffixes);
+    w.set(list, n);
+    w.set(list + 4, first);
+
+    let list = w.glob_list(&globs);
+    w.set(20, list);
+
+    let list = w.reserve(12);
+    w.set(24, list as u32);
+    let extent = magic
+        .iter()
+        .map(|(_, _, m)| matches_extent(m))
+        .max()
+        .unwrap_or(0);
+    w.set(list, magic.len() as u32);
+    w.set(list + 4, extent);
+    let first = w.reserve(magic.len() * MATCH_SIZE);
+    w.set(list + 8, first as u32);
+    for (i, (priority, typ, matches)) in magic.iter().enumerate() {
+        let entry = first + i * MATCH_SIZE;
+        let typ = w.string(typ);
+        let matchlets = w.matchlets(matches);
+        w.set(entry, *priority);
+        w.set(entry + 4, typ);
+        w.set(entry + 8, matches.len() as u32);
+        w.set(entry + 12, matchlets);
+    }
+
+    let list = w.reserve(4 + namespaces.len() * 12);
+    w.set(28, list as u32);
+    w.set(list, namespaces.len() as u32);
+    for (i, (ns, local, typ)) in namespaces.iter().enumerate() {
+        let entry = list + 4 + i * 12;
+        let ns = w.string(ns);
+        let local = w.string(local);
+        let typ = w.string(typ);
+        w.set(entry, ns);
+        w.set(entry + 4, local);
+        w.set(entry + 8, typ);
+    }
+
+    for (hdr, list) in [(32, &icons), (36, &generic_icons)] {
+        let start = w.reserve(4 + list.len() * 8);
+        w.set(hdr, start as u32);
+        w.set(start, list.len() as u32);
+        for (i, (typ, icon)) in list.iter().enumerate() {
+            let typ = w.string(typ);
+            let icon = w.string(icon);
+            w.set(start + 4 + i * 8, typ);
+            w.set(start + 8 + i * 8, icon);
+        }
+    }
+
+    w.data
+}
+
+/// Check whether a glob has no wildcards.
+fn is_literal(pattern: &str) -> bool {
+    !pattern.contains(['*', '?', '['])
+}
+
+/// Get the suffix of a `*.ext`-style glob, for the suffix tree.
+fn simple_suffix(pattern: &str) -> Option<&str> {
+    pattern
+        .strip_prefix('*')
+        .filter(|s| !s.is_empty() && is_literal(s))
+}
+
+/// Number of leading bytes needed to evaluate a list of magic matches.
+fn matches_extent(matches: &[MagicMatch]) -> u32 {
+    matches
+        .iter()
+        .map(|m| {
+            let own = m.offset + m.range_length + m.value.len() as u32;
+            own.max(matches_extent(&m.children))
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// A node in the reverse suffix tree under construction.
+#[derive(Default)]
+struct SuffixNode {
+    children: BTreeMap<char, SuffixNode>,
+    leaves: Vec<(String, u32)>,
+}
+
+impl SuffixNode {
+    fn add(&mut self, suffix: &str, typ: &str, weight: u32) {
+        let mut node = self;
+        for c in suffix.chars().rev() {
+            node = node.children.entry(c).or_default();
+        }
+        let leaf = (typ.to_string(), weight);
+        if !node.leaves.contains(&leaf) {
+            node.leaves.push(leaf);
+            node.leaves.sort();
+        }
+    }
+}
+
+/// Buffer for laying out a cache file.
+///
+/// Fixed-size lists are reserved first and filled in afterwards, so the
+/// strings and child lists they point to can be appended as they are found.
+#[derive(Default)]
+struct CacheWriter {
+    data: Vec<u8>,
+    strings: HashMap<String, u32>,
+}
+
+impl CacheWriter {
+    fn pos(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    /// Reserve a zeroed, aligned block, returning its offset.
+    fn reserve(&mut self, len: usize) -> usize {
+        self.align();
+        let start = self.data.len();
+        self.data.resize(start + len, 0);
+        start
+    }
+
+    fn align(&mut self) {
+        while !self.data.len().is_multiple_of(4) {
+            self.data.push(0);
+        }
+    }
+
+    fn set(&mut self, at: usize, val: u32) {
+        self.data[at..at + 4].copy_from_slice(&val.to_be_bytes());
+    }
+
+    /// Get the offset of a string, writing it if needed.
+    fn string(&mut self, s: &str) -> u32 {
+        if let Some(off) = self.strings.get(s) {
+            return *off;
+        }
+        let off = self.pos();
+        self.data.extend_from_slice(s.as_bytes());
+        self.data.push(0);
+        self.strings.insert(s.to_string(), off);
+        off
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> u32 {
+        let off = self.pos();
+        self.data.extend_from_slice(bytes);
+        off
+    }
+
+    fn glob_list(&mut self, globs: &[(String, &str, u32)]) -> u32 {
+        let list = self.reserve(4 + globs.len() * 12);
+        self.set(list, globs.len() as u32);
+        for (i, (pattern, typ, weight)) in globs.iter().enumerate() {
+            let entry = list + 4 + i * 12;
+            let pattern = self.string(pattern);
+            let typ = self.string(typ);
+            self.set(entry, pattern);
+            self.set(entry + 4, typ);
+            self.set(entry + 8, *weight);
+        }
+        list as u32
+    }
+
+    /// Write the children of a suffix tree node, returning their count and offset.
+    fn suffix_nodes(&mut self, node: &SuffixNode) -> (u32, u32) {
+        let n = node.leaves.len() + node.children.len();
+        let first = self.reserve(n * NODE_SIZE);
+        // leaves have character 0, so they sort first
+        for (i, (typ, weight)) in node.leaves.iter().enumerate() {
+            let entry = first + i * NODE_SIZE;
+            let typ = self.string(typ);
+            self.set(entry + 4, typ);
+            self.set(entry + 8, *weight);
+        }
+        for (i, (c, child)) in node.children.iter().enumerate() {
+            let entry = first + (node.leaves.len() + i) * NODE_SIZE;
+            let (n_children, first_child) = self.suffix_nodes(child);
+            self.set(entry, *c as u32);
+            self.set(entry + 4, n_children);
+            self.set(entry + 8, first_child);
+        }
+        (n as u32, first as u32)
+    }
+
+    /// Write a list of magic matchlets, returning its offset.
+    fn matchlets(&mut self, matches: &[MagicMatch]) -> u32 {
+        let first = self.reserve(matches.len() * MATCHLET_SIZE);
+        for (i, m) in matches.iter().enumerate() {
+            let entry = first + i * MATCHLET_SIZE;
+            let value = self.bytes(&m.value);
+            let mask = m.mask.as_ref().map_or(0, |mask| self.bytes(mask));
+            let children = if m.children.is_empty() {
+                0
+            } else {
+                self.matchlets(&m.children)
+            };
+            self.set(entry, m.offset);
+            self.set(entry + 4, m.range_length);
+            self.set(entry + 8, m.word_size);
+            self.set(entry + 12, m.value.len() as u32);
+            self.set(entry + 16, value);
+            self.set(entry + 20, mask);
+            self.set(entry + 24, m.children.len() as u32);
+            self.set(entry + 28, children);
+        }
+        first as u32
+    }
+}
+
+/// Check whether a MIME directory's cache is missing or older than its packages.
+pub fn cache_is_stale<P: AsRef<Path>>(dir: P) -> Result<bool, XDGError> {
+    let dir = dir.as_ref();
+    let cache = dir.join("mime.cache");
+    let cache_time = match fs::metadata(&cache) {
+        Ok(meta) => meta.modified()?,
+        Err(_) => return Ok(true),
+    };
+
+    let pkg_dir = dir.join("packages");
+    if !pkg_dir.try_exists()? {
+        return Ok(false);
+    }
+    let mut newest = fs::metadata(&pkg_dir)?.modified()?;
+    for entry in fs::read_dir(&pkg_dir)? {
+        let mtime = entry?.metadata()?.modified()?;
+        newest = newest.max(mtime);
+    }
+    if newest > cache_time {
+        debug!(
+            "{}: cache is {:?} older than packages",
+            dir.display(),
+            newest.duration_since(cache_time).unwrap_or_default()
+        );
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+#[test]
+fn test_read_minimal_cache() {
+    // strings go after the fixed-size lists built below
+    let strings_at = 156;
+    let typ = strings_at;
+    let alias = typ + "image/x-hackem\0".len();
+    let literal = alias + "image/x-muche\0".len();
+    let strings = b"image/x-hackem\0image/x-muche\0HACKEM\0";
+
+    let mut data: Vec<u8> = Vec::new();
+    let mut put = |vals: &[u32]| {
+        for v in vals {
+            data.extend_from_slice(&v.to_be_bytes());
+        }
+    };
+    // version 1.2, then list offsets
+    put(&[0x0001_0002, 40, 52, 56, 72, 128, 132, 144, 148, 152]);
+    // aliases (40), parents (52), literals (56)
+    put(&[1, alias as u32, typ as u32]);
+    put(&[0]);
+    put(&[1, literal as u32, typ as u32, 0x100 | 60]);
+    // suffix tree (72) for *.pg
+    put(&[1, 80]);
+    put(&['g' as u32, 1, 92]);
+    put(&['p' as u32, 1, 104]);
+    put(&['.' as u32, 1, 116]);
+    put(&[0, typ as u32, 50]);
+    // globs (128), magic (132), namespaces, icons, generic icons
+    put(&[0]);
+    put(&[0, 0, 0]);
+    put(&[0]);
+    put(&[0]);
+    put(&[0]);
+    assert_eq!(data.len(), strings_at);
+    data.extend_from_slice(strings);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("mime.cache");
+    fs::write(&path, &data).unwrap();
+    let records = MimeCache::open(&path).unwrap().records().unwrap();
+
+    assert_eq!(records.len(), 1);
+    let rec = &records[0];
+    assert_eq!(rec.name, "image/x-hackem");
+    assert_eq!(rec.aliases, vec!["image/x-muche"]);
+    assert_eq!(rec.globs.len(), 2);
+    assert_eq!(rec.globs[0].pattern, "HACKEM");
+    assert_eq!(rec.globs[0].weight, 60);
+    assert!(rec.globs[0].case_sensitive);
+    assert_eq!(rec.globs[1].pattern, "*.pg");
+    assert!(!rec.globs[1].case_sensitive);
+}
+
+#[test]
+fn test_write_cache() {
+    let rec = MimeTypeRecord {
+        name: "image/x-hackem".into(),
+        aliases: vec!["image/x-muche".into()],
+        superclasses: vec!["image/x-rogue".into()],
+        globs: vec![
+            glob_rule("HACKEM".into(), 0x100 | 60),
+            glob_rule("*.PG".into(), 50),
+            glob_rule("hack[ae]m.*".into(), 40),
+        ],
+        magic: vec![MagicRule {
+            priority: 60,
+            matches: vec![MagicMatch {
+                offset: 0,
+                range_length: 1,
+                word_size: 1,
+                value: b"HKM".to_vec(),
+                mask: None,
+                children: vec![MagicMatch {
+                    offset: 4,
+                    range_length: 4,
+                    word_size: 1,
+                    value: b"\x01".to_vec(),
+                    mask: Some(b"\x0f".to_vec()),
+                    children: Vec::new(),
+                }],
+            }],
+        }],
+        root_xml: vec![RootXmlRule {
+            namespace_uri: "urn:hackem".into(),
+            local_name: "muche".into(),
+        }],
+        generic_icon: Some("image-x-generic".into()),
+        ..Default::default()
+    };
+    let data = write_cache(std::slice::from_ref(&rec));
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("mime.cache");
+    fs::write(&path, &data).unwrap();
+    let cache = MimeCache::open(&path).unwrap();
+    let records = cache.records().unwrap();
+    let magic = cache.u32_at(24).unwrap() as usize;
+    let extent = cache.u32_at(magic + 4).unwrap();
+    drop(cache);
+
+    assert_eq!(extent, 9);
+    assert_eq!(records.len(), 1);
+    let read = &records[0];
+    assert_eq!(read.aliases, rec.aliases);
+    assert_eq!(read.superclasses, rec.superclasses);
+    let globs: Vec<_> = read.globs.iter().map(|g| g.pattern.as_str()).collect();
+    assert_eq!(globs, vec!["HACKEM", "hack[ae]m.*", "*.pg"]);
+    assert!(read.globs[0].case_sensitive);
+    assert_eq!(read.magic.len(), 1);
+    assert_eq!(read.magic[0].matches[0].value, b"HKM");
+    assert_eq!(read.magic[0].matches[0].children[0].mask, Some(vec![0x0f]));
+    assert_eq!(read.root_xml[0].local_name, "muche");
+    assert_eq!(read.generic_icon.as_deref(), Some("image-x-generic"));
+}
@@ -1,7 +1,8 @@
 //! XDG Shared MIME Info database representation.
 
 use std::{
-    fs::read_dir,
+    fmt,
+    fs::{self, read_dir},
     path::{Path, PathBuf},
 };
 
@@ -10,13 +11,52 @@ use serde::Serialize;
 
 use crate::record::MimeTypeRecord;
 
-use super::{parse_mime_package, xdg_mime_search_dirs, XDGError};
+use super::{
+    generated::{add_treemagic, add_type_files, has_generated_files, load_generated_files_with},
+    mime_cache::{cache_is_stale, MimeCache},
+    xdg_mime_search_dirs,
+    xdg_package::MimeInfoPackage,
+    xdg_parse::parse_package_located,
+    XDGError,
+};
 
 /// Container for the fully-parsed Shared Mime Info across search directories.
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, Default)]
 pub struct SharedMimeInfo {
     /// The XDG mime directories in processing order.
     pub directories: Vec<SMIDir>,
+    /// Problems with package (and per-type XML) files that were skipped in
+    /// [LoadMode::Lenient].
+    pub diagnostics: Vec<PackageDiagnostic>,
+}
+
+/// How to handle package files that cannot be parsed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Fail the whole load.
+    #[default]
+    Strict,
+    /// Skip the package, and record a [PackageDiagnostic].
+    Lenient,
+}
+
+/// A package file that could not be parsed.
+///
+/// Per-type XML files written by `update-mime-database` that cannot be parsed
+/// are always skipped, and also reported with this type.
+///
+/// Errors are located where the parser detected them.  For syntax errors that
+/// is the error itself; for invalid content (such as a missing attribute) it
+/// is the end of the element being read, which may be just past the problem.
+/// The location is unknown only if the file could not be read.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PackageDiagnostic {
+    pub path: PathBuf,
+    /// The line of the error (starting at 1), if known.
+    pub line: Option<usize>,
+    /// The column of the error (starting at 1), if known.
+    pub column: Option<usize>,
+    pub error: String,
 }
 
 /// A single directory in the shared mime database.
@@ -35,48 +75,113 @@ pub struct SMIPackage {
 
 /// Load the full XDG mime info database from all available files.
 pub fn load_xdg_mime_info() -> Result<SharedMimeInfo, XDGError> {
-    let dirs = xdg_mime_search_dirs();
-    Ok(SharedMimeInfo {
-        directories: dirs
-            .into_iter()
-            .filter_map(|d| load_xdg_mime_dir(d).transpose())
-            .collect::<Result<Vec<_>, _>>()?,
+    load_xdg_mime_info_with(LoadMode::Strict)
+}
+
+/// Load the full XDG mime info database, handling bad packages as specified.
+pub fn load_xdg_mime_info_with(mode: LoadMode) -> Result<SharedMimeInfo, XDGError> {
+    let mut info = SharedMimeInfo::default();
+    for dir in xdg_mime_search_dirs() {
+        if let Some(d) = load_xdg_mime_dir(dir, mode, &mut info.diagnostics)? {
+            info.directories.push(d);
+        }
+    }
+    Ok(info)
+}
+
+/// Load the XDG mime info database, using `mime.cache` files where possible.
+///
+/// Directories whose cache is missing, stale (older than their packages) or
+/// unreadable are loaded from their package XML files instead, handling bad
+/// packages as specified.  The cache does not contain descriptions or tree
+/// magic, so they are read from the per-type XML files and `treemagic`.
+pub fn load_xdg_mime_info_cached(mode: LoadMode) -> Result<SharedMimeInfo, XDGError> {
+    let mut info = SharedMimeInfo::default();
+    for dir in xdg_mime_search_dirs() {
+        let loaded = if cache_is_stale(&dir)? {
+            debug!("{}: cache missing or stale", dir.display());
+            None
+        } else {
+            match load_xdg_mime_cache(&dir, mode, &mut info.diagnostics) {
+                Ok(d) => Some(d),
+                Err(e) => {
+                    warn!("{}: error reading cache: {}", dir.display(), e);
+                    None
+                }
+            }
+        };
+        if let Some(d) = loaded {
+            info.directories.push(d);
+        } else if let Some(d) = load_xdg_mime_dir(&dir, mode, &mut info.diagnostics)? {
+            info.directories.push(d);
+        }
+    }
+    Ok(info)
+}
+
+fn load_xdg_mime_cache(
+    path: &Path,
+    mode: LoadMode,
+    diagnostics: &mut Vec<PackageDiagnostic>,
+) -> Result<SMIDir, XDGError> {
+    let file = path.join("mime.cache");
+    debug!("reading cache file {}", file.display());
+    let cache = MimeCache::open(&file)?;
+    let mut types = cache.records()?;
+    add_treemagic(path, &mut types)?;
+    add_type_files(path, &mut types, mode, diagnostics)?;
+    Ok(SMIDir {
+        path: path.to_path_buf(),
+        packages: vec![SMIPackage {
+            filename: "mime.cache".into(),
+            types,
+        }],
     })
 }
 
-fn load_xdg_mime_dir<P: AsRef<Path>>(path: P) -> Result<Option<SMIDir>, XDGError> {
+fn load_xdg_generated(
+    path: &Path,
+    mode: LoadMode,
+    diagnostics: &mut Vec<PackageDiagnostic>,
+) -> Result<Option<SMIDir>, XDGError> {
+    if !has_generated_files(path) {
+        return Ok(None);
+    }
+    debug!("reading generated files in {}", path.display());
+    Ok(Some(SMIDir {
+        path: path.to_path_buf(),
+        packages: vec![SMIPackage {
+            filename: "generated".into(),
+            types: load_generated_files_with(path, mode, diagnostics)?,
+        }],
+    }))
+}
+
+pub(crate) fn load_xdg_mime_dir<P: AsRef<Path>>(
+    path: P,
+    mode: LoadMode,
+    diagnostics: &mut Vec<PackageDiagnostic>,
+) -> Result<Option<SMIDir>, XDGError> {
     let path = path.as_ref();
     let mut buf = path.to_path_buf();
     buf.push("packages");
     debug!("looking for packages in {}", path.display());
     if !buf.try_exists()? {
         debug!("{} does not exist", buf.display());
-        return Ok(None);
+        return load_xdg_generated(path, mode, diagnostics);
     }
 
     let mut packages = Vec::new();
-    for entry in read_dir(&buf)? {
-        let entry = entry?;
-        let ep = entry.path();
-        let ext = ep.extension().map_or("".into(), |e| e.to_string_lossy());
-        debug!("extension {}", ext);
-        if entry.file_name().as_encoded_bytes()[0] != b'.' && ext == "xml" {
-            debug!("reading package file {}", ep.display());
-            packages.push(SMIPackage {
-                filename: ep
-                    .file_name()
-                    .ok_or(XDGError::Layout("package missing filename".into()))?
-                    .to_string_lossy()
-                    .to_string(),
-                types: parse_mime_package(&ep)?
-                    .types
-                    .into_iter()
-                    .map(MimeTypeRecord::from)
-                    .collect(),
-            })
-        } else {
-            debug!("ignoring file {}", ep.display());
-        }
+    for name in package_names(&buf)? {
+        let ep = buf.join(&name);
+        debug!("reading package file {}", ep.display());
+        let Some(pkg) = load_package(&ep, mode, diagnostics)? else {
+            continue;
+        };
+        packages.push(SMIPackage {
+            filename: name,
+            types: pkg.into_records(),
+        })
     }
 
     Ok(Some(SMIDir {
@@ -84,3 +189,317 @@ fn load_xdg_mime_dir<P: AsRef<Path>>(path: P) -> Result<Option<SMIDir>, XDGError
         packages,
     }))
 }
+
+/// List the package files in a `packages` directory, in the order
+/// `update-mime-database` applies them.
+pub(crate) fn package_names(pkg_dir: &Path) -> Result<Vec<String>, XDGError> {
+    let mut names = Vec::new();
+    for entry in read_dir(pkg_dir)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if !name.starts_with('.') && name.ends_with(".xml") {
+            names.push(name);
+        } else {
+            debug!("ignoring file {}", name);
+        }
+    }
+    // Override.xml is applied last, so local changes take precedence
+    names.sort_by_key(|n| (n == "Override.xml", n.clone()));
+    Ok(names)
+}
+
+/// Parse a package file, skipping it if it is broken and loading is lenient.
+pub(crate) fn load_package(
+    path: &Path,
+    mode: LoadMode,
+    diagnostics: &mut Vec<PackageDiagnostic>,
+) -> Result<Option<MimeInfoPackage>, XDGError> {
+    match parse_package_located(path) {
+        Ok(pkg) => Ok(Some(pkg)),
+        Err((e, pos)) if mode == LoadMode::Lenient => {
+            let diag = PackageDiagnostic::new(path, &e, pos);
+            warn!("skipping package: {}", diag);
+            diagnostics.push(diag);
+            Ok(None)
+        }
+        Err((e, _pos)) => Err(e),
+    }
+}
+
+impl PackageDiagnostic {
+    pub(crate) fn new(path: &Path, error: &XDGError, pos: Option<usize>) -> PackageDiagnostic {
+        // the parser only reports a byte offset, so re-read the file to find
+        // its line and column
+        let (line, column) = match (pos, fs::read(path)) {
+            (Some(pos), Ok(data)) => {
+                let (line, column) = line_column(&data, pos);
+                (Some(line), Some(column))
+            }
+            _ => (None, None),
+        };
+        PackageDiagnostic {
+            path: path.to_path_buf(),
+            line,
+            column,
+            error: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for PackageDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+/// Convert a byte offset into a line and column, both starting at 1.
+fn line_column(data: &[u8], pos: usize) -> (usize, usize) {
+    let before = &data[..pos.min(data.len())];
+    let line = before.iter().filter(|b| **b == b'\n').count() + 1;
+    let start = before
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |i| i + 1);
+    (line, pos - start + 1)
+}
+
+#[test]
+fn test_line_column() {
+    let data = b"<a>\n  <b>\n</a>";
+    assert_eq!(line_column(data, 0), (1, 1));
+    assert_eq!(line_column(data, 6), (2, 3));
+}
+
+#[test]
+fn test_lenient_dir() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    fs::create_dir_all(dir.join("packages")).unwrap();
+    fs::write(
+        dir.join("packages/good.xml"),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<mime-info xmlns="http://www.freedesktop.org/standards/shared-mime-info">
+  <mime-type type="text/x-hackem">
+    <glob pattern="*.hkm"/>
+  </mime-type>
+</mime-info>
+"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.join("packages/bad.xml"),
+        "<mime-info>\n  <mime-type type=\"text/x-muche\">\n</mime-info>\n",
+    )
+    .unwrap();
+
+    let mut diagnostics = Vec::new();
+    let strict = load_xdg_mime_dir(dir, LoadMode::Strict, &mut diagnostics);
+    let lenient = load_xdg_mime_dir(dir, LoadMode::Lenient, &mut diagnostics);
+
+    assert!(strict.is_err());
+    let lenient = lenient.unwrap().unwrap();
+    assert_eq!(lenient.packages.len(), 1);
+    assert_eq!(lenient.packages[0].filename, "good.xml");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].path, dir.join("packages/bad.xml"));
+    assert_eq!(diagnostics[0].line, Some(3));
+}
+
+#[test]
+fn test_override_order() {
+    use crate::MimeDB;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    fs::create_dir_all(dir.join("packages")).unwrap();
+    let package = |name: &str, body: &str| {
+        fs::write(
+            dir.join("packages").join(name),
+            format!(
+                "<mime-info xmlns=\"http://www.freedesktop.org/standards/shared-mime-info\">\n\
+                 <mime-type type=\"text/x-hackem\">{}</mime-type>\n</mime-info>\n",
+                body
+            ),
+        )
+        .unwrap();
+    };
+    // Override.xml sorts first by name, but is applied last
+    package("e-vendor.xml", r#"<glob pattern="*.hkm"/>"#);
+    package(
+        "Override.xml",
+        r#"<glob-deleteall/><glob pattern="*.muche"/>"#,
+    );
+
+    let mut diagnostics = Vec::new();
+    let smi = load_xdg_mime_dir(dir, LoadMode::Strict, &mut diagnostics)
+        .unwrap()
+        .unwrap();
+    let names: Vec<_> = smi.packages.iter().map(|p| p.filename.as_str()).collect();
+    assert_eq!(names, vec!["e-vendor.xml", "Override.xml"]);
+
+    let mut db = MimeDB::new();
+    db.add_shared_mime_info(SharedMimeInfo {
+        directories: vec![smi],
+        ..Default::default()
+    });
+    assert!(db.query_filename("a.hkm").is_unknown());
+    assert_eq!(db.query_filename("a.muche").best(), Some("text/x-hackem"));
+}
+
+#[test]
+fn test_lenient_generated_dir() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    fs::write(
+        dir.join("globs2"),
+        "50:text/x-hackem:*.hkm\nbogus\nx:text/x-muche:*.muc\n",
+    )
+    .unwrap();
+    fs::write(dir.join("aliases"), "text/x-muche\n").unwrap();
+    fs::create_dir(dir.join("text")).unwrap();
+    fs::write(dir.join("text/x-broken.xml"), "<mime-type").unwrap();
+
+    let mut diagnostics = Vec::new();
+    let smi = load_xdg_mime_dir(dir, LoadMode::Lenient, &mut diagnostics)
+        .unwrap()
+        .unwrap();
+    let types = &smi.packages[0].types;
+    assert_eq!(types.len(), 1);
+    assert_eq!(types[0].name, "text/x-hackem");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].path, dir.join("text/x-broken.xml"));
+
+    // the type file is skipped, even when strict
+    let mut diagnostics = Vec::new();
+    assert!(load_xdg_mime_dir(dir, LoadMode::Strict, &mut diagnostics).is_ok());
+    assert!(diagnostics.is_empty());
+}
+
+#[test]
+fn test_cache_matches_packages() {
+    use crate::record::{glob_record, MagicMatch, MagicRule};
+    use crate::MimeDB;
+
+    // update-mime-database output for the fixture package
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/hackem");
+    let load = |smi: SMIDir| {
+        let mut db = MimeDB::new();
+        // a lower directory, whose rules the fixture deletes
+        db.add_records(vec![MimeTypeRecord {
+            magic: vec![MagicRule {
+                priority: 50,
+                matches: vec![MagicMatch {
+                    offset: 0,
+                    range_length: 1,
+                    word_size: 1,
+                    value: b"OLD!".to_vec(),
+                    mask: None,
+                    children: vec![],
+                }],
+            }],
+            ..glob_record("application/x-hackem", &[("*.old", 50)])
+        }]);
+        db.add_shared_mime_info(SharedMimeInfo {
+            directories: vec![smi],
+            ..Default::default()
+        });
+        db
+    };
+    let xml = load(
+        load_xdg_mime_dir(&dir, LoadMode::Strict, &mut Vec::new())
+            .unwrap()
+            .unwrap(),
+    );
+    let cache = load(load_xdg_mime_cache(&dir, LoadMode::Strict, &mut Vec::new()).unwrap());
+
+    assert_eq!(cache.type_count(), xml.type_count());
+    assert_eq!(cache.glob_count(), xml.glob_count());
+    assert_eq!(cache.magic_count(), xml.magic_count());
+    assert_eq!(cache.treemagic_count(), xml.treemagic_count());
+    assert_eq!(cache.treemagic_count(), 1);
+    for name in [
+        "a.old",
+        "a.hkm",
+        "A.HKM",
+        "a.hkz",
+        "HACKEM",
+        "hackem",
+        "hackem-1.dat",
+        "a.rgs",
+    ] {
+        assert_eq!(
+            cache.query_filename(name).all_types(),
+            xml.query_filename(name).all_types(),
+            "{}",
+            name
+        );
+    }
+    for data in [
+        &b"OLD!"[..],
+        b"HKM!\x01\x02",
+        b"HKM!\x01\xf2",
+        b"HKM!\x02\x02",
+        b"<save xmlns=\"urn:x-rogue\"/>",
+    ] {
+        assert_eq!(
+            cache.query_magic(data).all_types(),
+            xml.query_magic(data).all_types()
+        );
+        assert_eq!(
+            cache.query_xml(data).all_types(),
+            xml.query_xml(data).all_types()
+        );
+    }
+    for typ in [
+        "application/x-hackem",
+        "text/x-rogue-save",
+        "text/x-rogue-note",
+        "x-content/x-rogue-game",
+    ] {
+        assert_eq!(cache.description(typ), xml.description(typ));
+        assert_eq!(
+            cache.description_for_locale(typ, &["de"]),
+            xml.description_for_locale(typ, &["de"])
+        );
+        assert_eq!(cache.acronym(typ), xml.acronym(typ));
+        assert_eq!(cache.expanded_acronym(typ), xml.expanded_acronym(typ));
+        assert_eq!(cache.aliases(typ), xml.aliases(typ));
+        assert_eq!(cache.parents(typ), xml.parents(typ));
+        assert_eq!(cache.icon_names(typ), xml.icon_names(typ));
+    }
+    assert!(cache.query_filename("a.old").is_unknown());
+    assert!(cache.query_magic(b"OLD!").is_unknown());
+    assert_eq!(cache.description("text/x-rogue-note"), Some("Rogue note"));
+
+    let tmp = tempfile::tempdir().unwrap();
+    fs::create_dir(tmp.path().join("ROGUE")).unwrap();
+    fs::write(tmp.path().join("ROGUE/rogue.sav"), b"").unwrap();
+    let tree = cache.query_tree(tmp.path()).unwrap();
+    assert_eq!(tree.best(), Some("x-content/x-rogue-game"));
+    assert_eq!(
+        tree.all_types(),
+        xml.query_tree(tmp.path()).unwrap().all_types()
+    );
+}
+
+#[test]
+fn test_invalid_package_location() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("invalid.xml");
+    // well-formed, but the type has no name
+    fs::write(
+        &path,
+        "<mime-info>\n  <mime-type>\n    <glob pattern=\"*.hkm\"/>\n  </mime-type>\n</mime-info>\n",
+    )
+    .unwrap();
+
+    let mut diagnostics = Vec::new();
+    let pkg = load_package(&path, LoadMode::Lenient, &mut diagnostics).unwrap();
+    assert!(pkg.is_none());
+    assert_eq!(diagnostics.len(), 1);
+    // found once the element (and the whitespace after it) has been read
+    assert_eq!(diagnostics[0].line, Some(5));
+}
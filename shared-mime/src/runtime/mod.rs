@@ -13,10 +13,28 @@
 //! - The same parsing code can directly load the XML from the
 //!   `shared-mime-info` source repository for embedding.
 //!
+//! Directories that only contain the generated files (no `packages/`) are
+//! loaded from those files instead; see [generated].
+//!
+//! [update_mime_database] generates those files from the packages, like the C
+//! `update-mime-database` tool.
+//!
+//! [MimeDbLoader] builds a database from explicit directories and packages,
+//! without consulting the environment.
+//!
+//! Where startup time matters more, [load_xdg_mime_info_cached] reads the
+//! binary `mime.cache` files instead, falling back to the XML when a cache is
+//! missing or out of date.
+//!
 //! [SMI]:
 //!     https://specifications.freedesktop.org/shared-mime-info-spec/shared-mime-info-spec-latest.html
 mod dirs;
+pub mod generated;
+mod loader;
+pub mod mime_cache;
 pub mod mimeinfo;
+pub mod update;
+mod xdg_magic;
 pub mod xdg_package;
 mod xdg_parse;
 
@@ -26,8 +44,13 @@ use quick_xml::DeError;
 use thiserror::Error;
 
 pub use dirs::xdg_mime_search_dirs;
-pub use mimeinfo::load_xdg_mime_info;
-pub use xdg_parse::parse_mime_package;
+pub use loader::MimeDbLoader;
+pub use mimeinfo::{
+    load_xdg_mime_info, load_xdg_mime_info_cached, load_xdg_mime_info_with, LoadMode,
+    PackageDiagnostic,
+};
+pub use update::update_mime_database;
+pub use xdg_parse::{parse_mime_package, parse_mime_package_bytes};
 
 /// Error type for mime-info parse failures.
 #[derive(Error, Debug)]
@@ -38,4 +61,8 @@ pub enum XDGError {
     Deserialize(#[from] DeError),
     #[error("layout error: {0}")]
     Layout(String),
+    #[error("MIME cache error: {0}")]
+    Cache(String),
+    #[error("format error: {0}")]
+    Format(String),
 }
@@ -0,0 +1,627 @@
+//! Generate the derived files for a MIME directory, like `update-mime-info`.
+//!
+//! [update_mime_database] reads the package XML from a directory's `packages/`
+//! subdirectory, merges it, and writes the text and binary files that other
+//! readers (such as GIO and the rest of this crate) use.
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs::{self, read_dir};
+use std::path::Path;
+
+use log::*;
+
+use crate::record::{MagicMatch, MimeTypeRecord, TreeFileType, TreeMatch};
+
+use super::{
+    mime_cache::write_cache,
+    mimeinfo::{package_names, SMIPackage},
+    parse_mime_package, XDGError,
+};
+
+const GLOBS_HEADER: &str =
+    "# This file was automatically generated by the\n# update-mime-database command. DO NOT EDIT!\n";
+const XML_MARKER: &str = "<!--Created automatically by update-mime-database. DO NOT EDIT!-->";
+const SMI_NAMESPACE: &str = "http://www.freedesktop.org/standards/shared-mime-info";
+
+/// Regenerate the derived files in a MIME directory from its packages.
+///
+/// This writes `globs`, `globs2`, `magic`, `treemagic`, `aliases`,
+/// `subclasses`, `types`, `icons`, `generic-icons`, `XMLnamespaces`,
+/// `mime.cache` and the per-type XML files, replacing each file atomically.
+pub fn update_mime_database<P: AsRef<Path>>(dir: P) -> Result<(), XDGError> {
+    let dir = dir.as_ref();
+    let packages = load_packages(dir)?;
+    info!("{}: merging {} packages", dir.display(), packages.len());
+    let records = merge_packages(packages);
+    write_mime_files(dir, &records)
+}
+
+/// Load the packages in a directory, in the order `update-mime-info` applies them.
+fn load_packages(dir: &Path) -> Result<Vec<SMIPackage>, XDGError> {
+    let pkg_dir = dir.join("packages");
+    if !pkg_dir.is_dir() {
+        return Err(XDGError::Layout(format!(
+            "{} does not exist",
+            pkg_dir.display()
+        )));
+    }
+    let names = package_names(&pkg_dir)?;
+    let mut packages = Vec::with_capacity(names.len());
+    for name in names {
+        let path = pkg_dir.join(&name);
+        debug!("reading package file {}", path.display());
+        let types = parse_mime_package(&path)?.into_records();
+        packages.push(SMIPackage {
+            filename: name,
+            types,
+        });
+    }
+    Ok(packages)
+}
+
+/// Merge the packages from a single MIME directory into one record per type.
+///
+/// Later packages extend the types from earlier ones with
+/// [MimeTypeRecord::merge].  Glob and magic deletions are kept in the merged
+/// records, so they still apply to lower directories when the generated files
+/// are read.
+pub fn merge_packages(packages: Vec<SMIPackage>) -> Vec<MimeTypeRecord> {
+    let mut types: BTreeMap<String, MimeTypeRecord> = BTreeMap::new();
+    for pkg in packages {
+        for rec in pkg.types {
+            match types.entry(rec.name.clone()) {
+                Entry::Vacant(e) => {
+                    let merged = e.insert(MimeTypeRecord {
+                        name: rec.name.clone(),
+                        ..Default::default()
+                    });
+                    merged.merge(rec);
+                }
+                Entry::Occupied(mut e) => e.get_mut().merge(rec),
+            }
+        }
+    }
+    types.into_values().collect()
+}
+
+/// Write the derived files for merged type records into a MIME directory.
+pub fn write_mime_files<P: AsRef<Path>>(
+    dir: P,
+    records: &[MimeTypeRecord],
+) -> Result<(), XDGError> {
+    let dir = dir.as_ref();
+    write_file(dir, "globs", globs_file(records).as_bytes())?;
+    write_file(dir, "globs2", globs2_file(records).as_bytes())?;
+    write_file(dir, "magic", &magic_file(records))?;
+    write_file(dir, "treemagic", &treemagic_file(records))?;
+    write_file(dir, "aliases", aliases_file(records).as_bytes())?;
+    write_file(dir, "subclasses", subclasses_file(records).as_bytes())?;
+    write_file(dir, "types", types_file(records).as_bytes())?;
+    write_file(dir, "icons", icons_file(records, |r| &r.icon).as_bytes())?;
+    write_file(
+        dir,
+        "generic-icons",
+        icons_file(records, |r| &r.generic_icon).as_bytes(),
+    )?;
+    write_file(dir, "XMLnamespaces", namespaces_file(records).as_bytes())?;
+    write_file(dir, "mime.cache", &write_cache(records))?;
+    write_type_xml(dir, records)
+}
+
+/// Write a file by writing a temporary file and renaming it into place.
+fn write_file(dir: &Path, name: &str, data: &[u8]) -> Result<(), XDGError> {
+    let path = dir.join(name);
+    let tmp = dir.join(format!("{}.new", name));
+    debug!("writing {} bytes to {}", data.len(), path.display());
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// Get the glob lines (weight, type, pattern, case-sensitive), highest weight first.
+///
+/// Patterns that are not case-sensitive are lower-cased, as readers match them
+/// against the lower-cased file name.
+fn glob_lines(records: &[MimeTypeRecord]) -> Vec<(i32, &str, String, bool)> {
+    let mut lines = Vec::new();
+    for rec in records {
+        for glob in rec.globs.iter() {
+            let pattern = if glob.case_sensitive {
+                glob.pattern.clone()
+            } else {
+                glob.pattern.to_lowercase()
+            };
+            lines.push((glob.weight, rec.name.as_str(), pattern, glob.case_sensitive));
+        }
+    }
+    lines.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.1.cmp(b.1))
+            .then_with(|| a.2.cmp(&b.2))
+            .then_with(|| b.3.cmp(&a.3))
+    });
+    lines.dedup();
+    lines
+}
+
+fn globs_file(records: &[MimeTypeRecord]) -> String {
+    let mut out = GLOBS_HEADER.to_string();
+    for rec in records.iter().filter(|r| r.glob_deleteall) {
+        writeln!(out, "{}:__NOGLOBS__", rec.name).unwrap();
+    }
+    let mut seen = BTreeSet::new();
+    for (_, typ, pattern, _) in glob_lines(records) {
+        if seen.insert((typ, pattern.clone())) {
+            writeln!(out, "{}:{}", typ, pattern).unwrap();
+        }
+    }
+    out
+}
+
+fn globs2_file(records: &[MimeTypeRecord]) -> String {
+    let mut out = GLOBS_HEADER.to_string();
+    // deletions must come before this directory's own globs for the type
+    for rec in records.iter().filter(|r| r.glob_deleteall) {
+        writeln!(out, "0:{}:__NOGLOBS__", rec.name).unwrap();
+    }
+    for (weight, typ, pattern, cs) in glob_lines(records) {
+        if cs {
+            writeln!(out, "{}:{}:{}:cs", weight, typ, pattern).unwrap();
+        }
+        // older readers ignore the flags, so every glob gets a plain line
+        writeln!(out, "{}:{}:{}", weight, typ, pattern).unwrap();
+    }
+    out
+}
+
+fn aliases_file(records: &[MimeTypeRecord]) -> String {
+    let mut pairs: Vec<(&str, &str)> = records
+        .iter()
+        .flat_map(|r| r.aliases.iter().map(|a| (a.as_str(), r.name.as_str())))
+        .collect();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(a, t)| format!("{} {}\n", a, t))
+        .collect()
+}
+
+fn subclasses_file(records: &[MimeTypeRecord]) -> String {
+    records
+        .iter()
+        .flat_map(|r| r.superclasses.iter().map(|s| format!("{} {}\n", r.name, s)))
+        .collect()
+}
+
+fn types_file(records: &[MimeTypeRecord]) -> String {
+    records.iter().map(|r| format!("{}\n", r.name)).collect()
+}
+
+fn icons_file<F>(records: &[MimeTypeRecord], icon: F) -> String
+where
+    F: Fn(&MimeTypeRecord) -> &Option<String>,
+{
+    records
+        .iter()
+        .filter_map(|r| icon(r).as_ref().map(|i| format!("{}:{}\n", r.name, i)))
+        .collect()
+}
+
+fn namespaces_file(records: &[MimeTypeRecord]) -> String {
+    let mut lines: Vec<(&str, &str, &str)> = records
+        .iter()
+        .flat_map(|r| {
+            r.root_xml.iter().map(|rx| {
+                (
+                    rx.namespace_uri.as_str(),
+                    rx.local_name.as_str(),
+                    r.name.as_str(),
+                )
+            })
+        })
+        .collect();
+    lines.sort();
+    lines
+        .iter()
+        .map(|(ns, local, typ)| format!("{} {} {}\n", ns, local, typ))
+        .collect()
+}
+
+/// Get a directory's magic or tree magic rules, highest priority first.
+fn sorted_rules<'a, R, F>(records: &'a [MimeTypeRecord], rules: F) -> Vec<(&'a str, &'a R)>
+where
+    F: Fn(&'a MimeTypeRecord) -> &'a [R],
+    R: Priority,
+{
+    let mut out: Vec<(&str, &R)> = records
+        .iter()
+        .flat_map(|r| rules(r).iter().map(|rule| (r.name.as_str(), rule)))
+        .collect();
+    out.sort_by(|a, b| {
+        b.1.priority()
+            .cmp(&a.1.priority())
+            .then_with(|| a.0.cmp(b.0))
+    });
+    out
+}
+
+trait Priority {
+    fn priority(&self) -> u32;
+}
+
+impl Priority for crate::record::MagicRule {
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+}
+
+impl Priority for crate::record::TreeMagicRule {
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+}
+
+fn magic_file(records: &[MimeTypeRecord]) -> Vec<u8> {
+    let mut out = b"MIME-Magic\0\n".to_vec();
+    // deletions must come before this directory's own rules for the type
+    for rec in records.iter().filter(|r| r.magic_deleteall) {
+        out.extend_from_slice(format!("[0:{}]\n", rec.name).as_bytes());
+        out.extend_from_slice(b">0=\0\x0b__NOMAGIC__\n");
+    }
+    for (typ, rule) in sorted_rules(records, |r| &r.magic) {
+        out.extend_from_slice(format!("[{}:{}]\n", rule.priority, typ).as_bytes());
+        write_magic_matches(&mut out, &rule.matches, 0);
+    }
+    out
+}
+
+fn write_magic_matches(out: &mut Vec<u8>, matches: &[MagicMatch], indent: u32) {
+    for m in matches {
+        if indent > 0 {
+            out.extend_from_slice(indent.to_string().as_bytes());
+        }
+        out.extend_from_slice(format!(">{}=", m.offset).as_bytes());
+        out.extend_from_slice(&(m.value.len() as u16).to_be_bytes());
+        out.extend_from_slice(&m.value);
+        if let Some(mask) = &m.mask {
+            out.push(b'&');
+            out.extend_from_slice(mask);
+        }
+        if m.word_size != 1 {
+            out.extend_from_slice(format!("~{}", m.word_size).as_bytes());
+        }
+        if m.range_length != 1 {
+            out.extend_from_slice(format!("+{}", m.range_length).as_bytes());
+        }
+        out.push(b'\n');
+        write_magic_matches(out, &m.children, indent + 1);
+    }
+}
+
+fn treemagic_file(records: &[MimeTypeRecord]) -> Vec<u8> {
+    let mut out = b"MIME-TreeMagic\0\n".to_vec();
+    for (typ, rule) in sorted_rules(records, |r| &r.treemagic) {
+        out.extend_from_slice(format!("[{}:{}]\n", rule.priority, typ).as_bytes());
+        write_tree_matches(&mut out, &rule.matches, 0);
+    }
+    out
+}
+
+fn write_tree_matches(out: &mut Vec<u8>, matches: &[TreeMatch], indent: u32) {
+    for tm in matches {
+        let mut line = String::new();
+        if indent > 0 {
+            write!(line, "{}", indent).unwrap();
+        }
+        let file_type = match tm.file_type {
+            Some(TreeFileType::File) => "file",
+            Some(TreeFileType::Directory) => "directory",
+            Some(TreeFileType::Link) => "link",
+            None => "any",
+        };
+        write!(line, ">\"{}\"={}", tm.path, file_type).unwrap();
+        if tm.executable {
+            line.push_str(",executable");
+        }
+        if tm.match_case {
+            line.push_str(",match-case");
+        }
+        if tm.non_empty {
+            line.push_str(",non-empty");
+        }
+        if let Some(mt) = &tm.mimetype {
+            write!(line, ",{}", mt).unwrap();
+        }
+        line.push('\n');
+        out.extend_from_slice(line.as_bytes());
+        write_tree_matches(out, &tm.children, indent + 1);
+    }
+}
+
+/// Write the per-type XML files (`media/subtype.xml`), and remove stale ones.
+fn write_type_xml(dir: &Path, records: &[MimeTypeRecord]) -> Result<(), XDGError> {
+    let mut written = BTreeSet::new();
+    for rec in records {
+        // file names are lower-cased, as readers look them up that way
+        let lname = rec.name.to_lowercase();
+        let Some((media, subtype)) = lname.split_once('/') else {
+            warn!("{}: invalid type name, skipping XML", rec.name);
+            continue;
+        };
+        let media_dir = dir.join(media);
+        fs::create_dir_all(&media_dir)?;
+        let name = format!("{}.xml", subtype);
+        write_file(&media_dir, &name, type_xml(rec).as_bytes())?;
+        written.insert(media_dir.join(name));
+    }
+
+    let media_dirs: BTreeSet<_> = written.iter().filter_map(|p| p.parent()).collect();
+    for media_dir in media_dirs {
+        for entry in read_dir(media_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "xml") && !written.contains(&path) {
+                // only remove files we would have generated
+                let content = fs::read_to_string(&path).unwrap_or_default();
+                if content.contains(XML_MARKER) {
+                    debug!("removing stale {}", path.display());
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Render the XML file describing a single type.
+fn type_xml(rec: &MimeTypeRecord) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    writeln!(
+        out,
+        "<mime-type xmlns=\"{}\" type=\"{}\">",
+        SMI_NAMESPACE,
+        escape_attr(&rec.name)
+    )
+    .unwrap();
+    writeln!(out, "  {}", XML_MARKER).unwrap();
+    if let Some(desc) = &rec.description {
+        writeln!(out, "  <comment>{}</comment>", escape(desc)).unwrap();
+    }
+    for (lang, desc) in rec.localized_descriptions.iter() {
+        writeln!(
+            out,
+            "  <comment xml:lang=\"{}\">{}</comment>",
+            escape_attr(lang),
+            escape(desc)
+        )
+        .unwrap();
+    }
+    if let Some(acronym) = &rec.acronym {
+        writeln!(out, "  <acronym>{}</acronym>", escape(acronym)).unwrap();
+    }
+    if let Some(expanded) = &rec.expanded_acronym {
+        writeln!(
+            out,
+            "  <expanded-acronym>{}</expanded-acronym>",
+            escape(expanded)
+        )
+        .unwrap();
+    }
+    if let Some(icon) = &rec.icon {
+        writeln!(out, "  <icon name=\"{}\"/>", escape_attr(icon)).unwrap();
+    }
+    if let Some(icon) = &rec.generic_icon {
+        writeln!(out, "  <generic-icon name=\"{}\"/>", escape_attr(icon)).unwrap();
+    }
+    for sup in rec.superclasses.iter() {
+        writeln!(out, "  <sub-class-of type=\"{}\"/>", escape_attr(sup)).unwrap();
+    }
+    if rec.glob_deleteall {
+        out.push_str("  <glob-deleteall/>\n");
+    }
+    for glob in rec.globs.iter() {
+        write!(out, "  <glob pattern=\"{}\"", escape_attr(&glob.pattern)).unwrap();
+        if glob.weight != 50 {
+            write!(out, " weight=\"{}\"", glob.weight).unwrap();
+        }
+        if glob.case_sensitive {
+            out.push_str(" case-sensitive=\"true\"");
+        }
+        out.push_str("/>\n");
+    }
+    for alias in rec.aliases.iter() {
+        writeln!(out, "  <alias type=\"{}\"/>", escape_attr(alias)).unwrap();
+    }
+    for ext in rec.extensions.iter() {
+        write!(out, "  <{}", ext.name).unwrap();
+        for (name, value) in ext.attributes.iter() {
+            write!(out, " {}=\"{}\"", name, escape_attr(value)).unwrap();
+        }
+        out.push_str("/>\n");
+    }
+    out.push_str("</mime-type>\n");
+    out
+}
+
+/// Escape XML text content.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Escape a double-quoted XML attribute value.
+fn escape_attr(s: &str) -> String {
+    escape(s).replace('"', "&quot;")
+}
+
+#[cfg(test)]
+fn package(types: Vec<MimeTypeRecord>) -> SMIPackage {
+    SMIPackage {
+        filename: "test.xml".into(),
+        types,
+    }
+}
+
+#[test]
+fn test_merge_packages() {
+    use crate::record::glob_record;
+
+    let base = MimeTypeRecord {
+        description: Some("Hackem file".into()),
+        aliases: vec!["text/x-muche".into()],
+        ..glob_record("text/x-hackem", &[("*.hkm", 50)])
+    };
+    let over = MimeTypeRecord {
+        glob_deleteall: true,
+        aliases: vec!["text/x-muche".into()],
+        icon: Some("hackem".into()),
+        ..glob_record("text/x-hackem", &[("*.hackem", 50)])
+    };
+    let merged = merge_packages(vec![package(vec![base]), package(vec![over])]);
+    assert_eq!(merged.len(), 1);
+    let rec = &merged[0];
+    assert_eq!(rec.description.as_deref(), Some("Hackem file"));
+    assert_eq!(rec.icon.as_deref(), Some("hackem"));
+    assert_eq!(rec.aliases, vec!["text/x-muche"]);
+    assert!(rec.glob_deleteall);
+    assert_eq!(rec.globs.len(), 1);
+    assert_eq!(rec.globs[0].pattern, "*.hackem");
+}
+
+#[test]
+fn test_type_xml() {
+    let rec = MimeTypeRecord {
+        name: "text/x-hackem".into(),
+        description: Some("Hackem & muche".into()),
+        localized_descriptions: [("de".to_string(), "Hackem-Datei".to_string())].into(),
+        globs: vec![crate::record::GlobRule {
+            pattern: "*.HKM".into(),
+            weight: 60,
+            case_sensitive: true,
+        }],
+        ..Default::default()
+    };
+    let xml = type_xml(&rec);
+    assert!(xml.contains("<comment>Hackem &amp; muche</comment>"));
+    assert!(xml.contains("<comment xml:lang=\"de\">Hackem-Datei</comment>"));
+    assert!(xml.contains("<glob pattern=\"*.HKM\" weight=\"60\" case-sensitive=\"true\"/>"));
+    assert!(xml.ends_with("</mime-type>\n"));
+}
+
+#[test]
+fn test_update_round_trip() {
+    use super::generated::load_generated_files;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    fs::create_dir_all(dir.join("packages")).unwrap();
+    fs::write(
+        dir.join("packages/hackem.xml"),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<mime-info xmlns="http://www.freedesktop.org/standards/shared-mime-info">
+  <mime-type type="text/x-hackem">
+    <comment>Hackem file</comment>
+    <sub-class-of type="text/plain"/>
+    <alias type="text/x-muche"/>
+    <glob pattern="*.hkm"/>
+    <glob pattern="HACKEM" case-sensitive="true"/>
+    <magic priority="60">
+      <match type="string" value="HACKEM" offset="0:4">
+        <match type="big16" value="0x0102" offset="10"/>
+      </match>
+    </magic>
+    <root-XML namespaceURI="urn:hackem" localName="muche"/>
+  </mime-type>
+</mime-info>
+"#,
+    )
+    .unwrap();
+
+    update_mime_database(dir).unwrap();
+    let records = load_generated_files(dir).unwrap();
+    let cached = super::mime_cache::MimeCache::open(dir.join("mime.cache"))
+        .unwrap()
+        .records()
+        .unwrap();
+    let xml = fs::read_to_string(dir.join("text/x-hackem.xml")).unwrap();
+
+    for rec in [&records[0], &cached[0]] {
+        assert_eq!(rec.name, "text/x-hackem");
+        assert_eq!(rec.superclasses, vec!["text/plain"]);
+        assert_eq!(rec.aliases, vec!["text/x-muche"]);
+        assert_eq!(rec.globs.len(), 2);
+        assert_eq!(rec.magic[0].priority, 60);
+        assert_eq!(rec.magic[0].matches[0].range_length, 5);
+        assert_eq!(rec.magic[0].matches[0].children[0].value, vec![1, 2]);
+        assert_eq!(rec.root_xml[0].local_name, "muche");
+    }
+    assert!(xml.contains("<comment>Hackem file</comment>"));
+}
+
+#[test]
+fn test_update_matches_fixture() {
+    use super::mime_cache::MimeCache;
+
+    // packages/ and the files update-mime-database generated from them
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/hackem");
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    fs::create_dir_all(dir.join("packages")).unwrap();
+    fs::copy(
+        fixture.join("packages/hackem.xml"),
+        dir.join("packages/hackem.xml"),
+    )
+    .unwrap();
+    update_mime_database(dir).unwrap();
+
+    let read = |d: &Path, name: &str| fs::read(d.join(name)).unwrap();
+    let cache_records = |d: &Path| {
+        let mut records = MimeCache::open(d.join("mime.cache"))
+            .unwrap()
+            .records()
+            .unwrap();
+        for rec in records.iter_mut() {
+            rec.globs
+                .sort_by(|a, b| (&a.pattern, a.weight).cmp(&(&b.pattern, b.weight)));
+        }
+        format!("{:?}", records)
+    };
+    let mut mismatched = Vec::new();
+    for name in [
+        "globs",
+        "globs2",
+        "aliases",
+        "subclasses",
+        "types",
+        "icons",
+        "generic-icons",
+        "XMLnamespaces",
+    ] {
+        // lines with equal sort keys may be in a different order
+        let mut ours: Vec<_> = read(dir, name)
+            .split(|c| *c == b'\n')
+            .map(Vec::from)
+            .collect();
+        let mut theirs: Vec<_> = read(&fixture, name)
+            .split(|c| *c == b'\n')
+            .map(Vec::from)
+            .collect();
+        ours.sort();
+        theirs.sort();
+        if ours != theirs {
+            mismatched.push(name);
+        }
+    }
+    for name in ["magic", "treemagic"] {
+        if read(dir, name) != read(&fixture, name) {
+            mismatched.push(name);
+        }
+    }
+    let ours = cache_records(dir);
+    let theirs = cache_records(&fixture);
+
+    assert!(mismatched.is_empty(), "files differ: {:?}", mismatched);
+    assert_eq!(ours, theirs);
+}
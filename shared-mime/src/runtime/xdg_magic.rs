@@ -0,0 +1,202 @@
+//! Parse the values in magic `<match>` elements.
+//!
+//! Values are converted to the byte representation used by the compiled
+//! `magic` file: strings are unescaped, and numbers are converted to bytes in
+//! the specified byte order (with host-endian values stored big-endian and
+//! flagged with their word size).
+
+/// Parse a match offset (`N` or `start:end`) into a start offset and range length.
+pub fn parse_magic_offset(offset: &str) -> Result<(u32, u32), String> {
+    let offset = offset.trim();
+    if offset.is_empty() {
+        return Ok((0, 1));
+    }
+    if let Some((start, end)) = offset.split_once(':') {
+        let start = parse_u32(start)?;
+        let end = parse_u32(end)?;
+        if end < start {
+            return Err(format!("invalid offset range {}", offset));
+        }
+        Ok((start, end - start + 1))
+    } else {
+        Ok((parse_u32(offset)?, 1))
+    }
+}
+
+/// Parse a match value into its bytes and word size.
+pub fn parse_magic_value(match_type: &str, value: &str) -> Result<(Vec<u8>, u32), String> {
+    let bytes = match match_type {
+        "string" => return Ok((unescape(value)?, 1)),
+        "byte" => vec![parse_number(value)? as u8],
+        "big16" => (parse_number(value)? as u16).to_be_bytes().to_vec(),
+        "big32" => (parse_number(value)? as u32).to_be_bytes().to_vec(),
+        "little16" => (parse_number(value)? as u16).to_le_bytes().to_vec(),
+        "little32" => (parse_number(value)? as u32).to_le_bytes().to_vec(),
+        "host16" => return Ok(((parse_number(value)? as u16).to_be_bytes().to_vec(), 2)),
+        "host32" => return Ok(((parse_number(value)? as u32).to_be_bytes().to_vec(), 4)),
+        t => return Err(format!("unknown match type {}", t)),
+    };
+    Ok((bytes, 1))
+}
+
+/// Parse a match mask, checking that it is the same length as the value.
+pub fn parse_mask(match_type: &str, mask: &str, len: usize) -> Result<Vec<u8>, String> {
+    let bytes = if match_type == "string" {
+        parse_hex_bytes(mask)?
+    } else {
+        parse_magic_value(match_type, mask)?.0
+    };
+    if bytes.len() != len {
+        return Err(format!(
+            "mask {} has length {}, expected {}",
+            mask,
+            bytes.len(),
+            len
+        ));
+    }
+    Ok(bytes)
+}
+
+fn parse_u32(num: &str) -> Result<u32, String> {
+    num.trim()
+        .parse()
+        .map_err(|e| format!("invalid offset {}: {}", num, e))
+}
+
+/// Parse a number the way `strtoul` does with base 0.
+fn parse_number(num: &str) -> Result<u64, String> {
+    let num = num.trim();
+    let res = if let Some(hex) = num.strip_prefix("0x").or(num.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16)
+    } else if num.len() > 1 && num.starts_with('0') {
+        u64::from_str_radix(&num[1..], 8)
+    } else {
+        num.parse()
+    };
+    res.map_err(|e| format!("invalid number {}: {}", num, e))
+}
+
+fn parse_hex_bytes(hex: &str) -> Result<Vec<u8>, String> {
+    let digits = hex
+        .strip_prefix("0x")
+        .or(hex.strip_prefix("0X"))
+        .ok_or_else(|| format!("mask {} is not hexadecimal", hex))?;
+    if digits.len() % 2 != 0 {
+        return Err(format!("mask {} has odd length", hex));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|e| format!("invalid mask {}: {}", hex, e))
+        })
+        .collect()
+}
+
+/// Unescape a string value with C-style escapes.
+fn unescape(value: &str) -> Result<Vec<u8>, String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        if bytes[pos] != b'\\' {
+            out.push(bytes[pos]);
+            pos += 1;
+            continue;
+        }
+        pos += 1;
+        let Some(&c) = bytes.get(pos) else {
+            return Err(format!("trailing backslash in {}", value));
+        };
+        match c {
+            b'x' => {
+                let n = count_digits(&bytes[pos + 1..], 2, |c| c.is_ascii_hexdigit());
+                if n == 0 {
+                    return Err(format!("invalid hex escape in {}", value));
+                }
+                let digits = &value[pos + 1..pos + 1 + n];
+                out.push(u8::from_str_radix(digits, 16).map_err(|e| e.to_string())?);
+                pos += n + 1;
+            }
+            b'0'..=b'7' => {
+                let n = count_digits(&bytes[pos..], 3, |c| (b'0'..=b'7').contains(&c));
+                let digits = &value[pos..pos + n];
+                let code = u32::from_str_radix(digits, 8).map_err(|e| e.to_string())?;
+                out.push(code as u8);
+                pos += n;
+            }
+            b'n' => {
+                out.push(b'\n');
+                pos += 1;
+            }
+            b'r' => {
+                out.push(b'\r');
+                pos += 1;
+            }
+            b't' => {
+                out.push(b'\t');
+                pos += 1;
+            }
+            c => {
+                out.push(c);
+                pos += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn count_digits<F: Fn(u8) -> bool>(bytes: &[u8], max: usize, pred: F) -> usize {
+    bytes.iter().take(max).take_while(|c| pred(**c)).count()
+}
+
+#[test]
+fn test_offset_single() {
+    assert_eq!(parse_magic_offset("8"), Ok((8, 1)));
+}
+
+#[test]
+fn test_offset_range() {
+    assert_eq!(parse_magic_offset("0:64"), Ok((0, 65)));
+}
+
+#[test]
+fn test_unescape_plain() {
+    assert_eq!(unescape("%PDF-"), Ok(b"%PDF-".to_vec()));
+}
+
+#[test]
+fn test_unescape_escapes() {
+    assert_eq!(
+        unescape("\\x89PNG\\r\\n\\032\\0"),
+        Ok(b"\x89PNG\r\n\x1a\0".to_vec())
+    );
+    assert_eq!(unescape("{\\\\rtf"), Ok(b"{\\rtf".to_vec()));
+}
+
+#[test]
+fn test_number_values() {
+    assert_eq!(parse_magic_value("byte", "0x8a"), Ok((vec![0x8a], 1)));
+    assert_eq!(
+        parse_magic_value("big16", "0x9501"),
+        Ok((vec![0x95, 0x01], 1))
+    );
+    assert_eq!(
+        parse_magic_value("little32", "0x10000419"),
+        Ok((vec![0x19, 0x04, 0x00, 0x10], 1))
+    );
+    assert_eq!(
+        parse_magic_value("host32", "0xa1b2c3d4"),
+        Ok((vec![0xa1, 0xb2, 0xc3, 0xd4], 4))
+    );
+    assert_eq!(parse_magic_value("byte", "010"), Ok((vec![8], 1)));
+}
+
+#[test]
+fn test_string_mask() {
+    assert_eq!(
+        parse_mask("string", "0xffff00ff", 4),
+        Ok(vec![0xff, 0xff, 0x00, 0xff])
+    );
+    assert!(parse_mask("string", "0xffff", 4).is_err());
+}
@@ -5,9 +5,19 @@
 //!
 //! [xdg]:
 //!     https://specifications.freedesktop.org/shared-mime-info-spec/shared-mime-info-spec-latest.html
-use serde::Deserialize;
+use std::fmt;
 
-use crate::record::{GlobRule, MimeTypeRecord};
+use serde::de::{self, DeserializeSeed, EnumAccess, IgnoredAny, MapAccess, VariantAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use log::*;
+
+use crate::record::{
+    ExtensionElement, GlobRule, MagicMatch, MagicRule, MimeTypeRecord, RootXmlRule, TreeFileType,
+    TreeMagicRule, TreeMatch,
+};
+
+use super::xdg_magic::{parse_magic_offset, parse_magic_value, parse_mask};
 
 // Shared mime info database.
 #[derive(Deserialize, Debug, Clone)]
@@ -28,30 +38,134 @@ pub struct MimeType {
 }
 
 /// Element in a MIME definition.
-#[derive(Deserialize, Debug, Clone)]
-#[serde(rename_all = "kebab-case")]
+///
+/// Elements this crate does not know are kept as [MimeTypeElement::Unknown],
+/// so packages from newer versions of the spec can still be loaded.
+#[derive(Debug, Clone)]
 pub enum MimeTypeElement {
     Comment(CommentElement),
     Glob(GlobElement),
     GlobDeleteall,
     SubClassOf(TypeRefElement),
     Alias(TypeRefElement),
-    Acronym(String),
-    ExpandedAcronym(String),
+    Acronym(CommentElement),
+    ExpandedAcronym(CommentElement),
     Icon(IconElement),
-    GenericIcon,
-    Magic,
+    GenericIcon(IconElement),
+    Magic(MagicElement),
     MagicDeleteall,
     Match(MatchElement),
-    Treemagic,
-    #[serde(rename = "root-XML")]
-    RootXML,
+    Treemagic(TreeMagicElement),
+    RootXML(RootXMLElement),
+    Unknown(ExtensionElement),
 }
 
-/// Comment (description) from the MIME database.
+/// Names of the elements in [MimeTypeElement].
+const KNOWN_ELEMENTS: &[&str] = &[
+    "comment",
+    "glob",
+    "glob-deleteall",
+    "sub-class-of",
+    "alias",
+    "acronym",
+    "expanded-acronym",
+    "icon",
+    "generic-icon",
+    "magic",
+    "magic-deleteall",
+    "match",
+    "treemagic",
+    "root-XML",
+];
+
+impl<'de> Deserialize<'de> for MimeTypeElement {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        de.deserialize_enum("MimeTypeElement", KNOWN_ELEMENTS, ElementVisitor)
+    }
+}
+
+struct ElementVisitor;
+
+impl<'de> Visitor<'de> for ElementVisitor {
+    type Value = MimeTypeElement;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a MIME type element")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        use MimeTypeElement::*;
+
+        let (name, elt): (String, _) = data.variant()?;
+        Ok(match name.as_str() {
+            "comment" => Comment(elt.newtype_variant()?),
+            "glob" => Glob(elt.newtype_variant()?),
+            "glob-deleteall" => elt.unit_variant().map(|_| GlobDeleteall)?,
+            "sub-class-of" => SubClassOf(elt.newtype_variant()?),
+            "alias" => Alias(elt.newtype_variant()?),
+            "acronym" => Acronym(elt.newtype_variant()?),
+            "expanded-acronym" => ExpandedAcronym(elt.newtype_variant()?),
+            "icon" => Icon(elt.newtype_variant()?),
+            "generic-icon" => GenericIcon(elt.newtype_variant()?),
+            "magic" => Magic(elt.newtype_variant()?),
+            "magic-deleteall" => elt.unit_variant().map(|_| MagicDeleteall)?,
+            "match" => Match(elt.newtype_variant()?),
+            "treemagic" => Treemagic(elt.newtype_variant()?),
+            "root-XML" => RootXML(elt.newtype_variant()?),
+            _ => {
+                debug!("keeping unknown element <{}>", name);
+                Unknown(elt.newtype_variant_seed(UnknownElement(name))?)
+            }
+        })
+    }
+}
+
+/// Deserialize an unknown element, keeping its attributes and ignoring its
+/// content.
+struct UnknownElement(String);
+
+impl<'de> DeserializeSeed<'de> for UnknownElement {
+    type Value = ExtensionElement;
+
+    fn deserialize<D: Deserializer<'de>>(self, de: D) -> Result<Self::Value, D::Error> {
+        de.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for UnknownElement {
+    type Value = ExtensionElement;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "element <{}>", self.0)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut attributes = Vec::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if let Some(attr) = key.strip_prefix('@') {
+                attributes.push((attr.to_string(), map.next_value()?));
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(ExtensionElement {
+            name: self.0,
+            attributes,
+        })
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(ExtensionElement {
+            name: self.0,
+            attributes: Vec::new(),
+        })
+    }
+}
+
+/// Comment (description) or other translatable text from the MIME database.
 #[derive(Deserialize, Debug, Clone)]
 pub struct CommentElement {
-    #[serde(rename = "@lang")]
+    #[serde(rename = "@xml:lang")]
     pub lang: Option<String>,
 
     #[serde(rename = "$text")]
@@ -69,22 +183,73 @@ pub struct GlobElement {
     pub case_sensitive: bool,
 }
 
-/// Element that references another type.
+/// Element that names an icon.
 #[derive(Deserialize, Debug, Clone)]
 pub struct IconElement {
     #[serde(rename = "@name")]
     pub name: String,
 }
 
-/// Element that references another type.
+/// Magic element, containing a prioritized tree of matches.
+#[derive(Deserialize, Debug, Clone)]
+pub struct MagicElement {
+    #[serde(rename = "@priority")]
+    pub priority: Option<u32>,
+    #[serde(rename = "match", default)]
+    pub matches: Vec<MatchElement>,
+}
+
+/// Match element within a magic rule.
 #[derive(Deserialize, Debug, Clone)]
 pub struct MatchElement {
     #[serde(rename = "@type")]
     pub match_type: String,
+    /// Offset, either a single number or an inclusive range `start:end`.
     #[serde(rename = "@offset", default)]
-    pub offset: u64,
+    pub offset: String,
     #[serde(rename = "@value", default)]
     pub value: String,
+    #[serde(rename = "@mask")]
+    pub mask: Option<String>,
+    #[serde(rename = "match", default)]
+    pub children: Vec<MatchElement>,
+}
+
+/// Tree magic element, containing a prioritized tree of path matches.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TreeMagicElement {
+    #[serde(rename = "@priority")]
+    pub priority: Option<u32>,
+    #[serde(rename = "treematch", default)]
+    pub matches: Vec<TreeMatchElement>,
+}
+
+/// Path match element within a tree magic rule.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TreeMatchElement {
+    #[serde(rename = "@path")]
+    pub path: String,
+    #[serde(rename = "@type")]
+    pub file_type: Option<TreeFileType>,
+    #[serde(rename = "@match-case", default)]
+    pub match_case: bool,
+    #[serde(rename = "@executable", default)]
+    pub executable: bool,
+    #[serde(rename = "@non-empty", default)]
+    pub non_empty: bool,
+    #[serde(rename = "@mimetype")]
+    pub mimetype: Option<String>,
+    #[serde(rename = "treematch", default)]
+    pub children: Vec<TreeMatchElement>,
+}
+
+/// Root XML element, identifying XML documents by their root element.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RootXMLElement {
+    #[serde(rename = "@namespaceURI", default)]
+    pub namespace_uri: String,
+    #[serde(rename = "@localName")]
+    pub local_name: String,
 }
 
 /// Element that references another type.
@@ -103,7 +268,7 @@ impl MimeInfoPackage {
 impl From<MimeType> for MimeTypeRecord {
     fn from(mime: MimeType) -> Self {
         MimeTypeRecord {
-            name: mime.name,
+            name: mime.name.clone(),
             description: mime
                 .elements
                 .iter()
@@ -117,6 +282,37 @@ impl From<MimeType> for MimeTypeRecord {
                     _ => false,
                 })
                 .map(|c| c.value.clone()),
+            localized_descriptions: mime
+                .elements
+                .iter()
+                .filter_map(|e| match e {
+                    MimeTypeElement::Comment(CommentElement {
+                        lang: Some(lang),
+                        value,
+                    }) => Some((lang.clone(), value.clone())),
+                    _ => None,
+                })
+                .collect(),
+            icon: mime.elements.iter().find_map(|e| match e {
+                MimeTypeElement::Icon(i) => Some(i.name.clone()),
+                _ => None,
+            }),
+            generic_icon: mime.elements.iter().find_map(|e| match e {
+                MimeTypeElement::GenericIcon(i) => Some(i.name.clone()),
+                _ => None,
+            }),
+            acronym: mime.elements.iter().find_map(|e| match e {
+                MimeTypeElement::Acronym(CommentElement { lang: None, value }) => {
+                    Some(value.clone())
+                }
+                _ => None,
+            }),
+            expanded_acronym: mime.elements.iter().find_map(|e| match e {
+                MimeTypeElement::ExpandedAcronym(CommentElement { lang: None, value }) => {
+                    Some(value.clone())
+                }
+                _ => None,
+            }),
             globs: mime
                 .elements
                 .iter()
@@ -125,6 +321,10 @@ impl From<MimeType> for MimeTypeRecord {
                     _ => None,
                 })
                 .collect(),
+            glob_deleteall: mime
+                .elements
+                .iter()
+                .any(|e| matches!(e, MimeTypeElement::GlobDeleteall)),
             superclasses: mime
                 .elements
                 .iter()
@@ -141,10 +341,120 @@ impl From<MimeType> for MimeTypeRecord {
                     _ => None,
                 })
                 .collect(),
+            magic: mime
+                .elements
+                .iter()
+                .filter_map(|e| match e {
+                    MimeTypeElement::Magic(m) => Some(magic_rule(&mime.name, m)),
+                    _ => None,
+                })
+                .collect(),
+            treemagic: mime
+                .elements
+                .iter()
+                .filter_map(|e| match e {
+                    MimeTypeElement::Treemagic(tm) => Some(tm.into()),
+                    _ => None,
+                })
+                .collect(),
+            root_xml: mime
+                .elements
+                .iter()
+                .filter_map(|e| match e {
+                    MimeTypeElement::RootXML(rx) => Some(RootXmlRule {
+                        namespace_uri: rx.namespace_uri.clone(),
+                        local_name: rx.local_name.clone(),
+                    }),
+                    _ => None,
+                })
+                .collect(),
+            magic_deleteall: mime
+                .elements
+                .iter()
+                .any(|e| matches!(e, MimeTypeElement::MagicDeleteall)),
+            extensions: mime
+                .elements
+                .iter()
+                .filter_map(|e| match e {
+                    MimeTypeElement::Unknown(ext) => Some(ext.clone()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn magic_rule(name: &str, magic: &MagicElement) -> MagicRule {
+    MagicRule {
+        priority: magic.priority.unwrap_or(50),
+        matches: magic_matches(name, &magic.matches),
+    }
+}
+
+/// Convert match elements, skipping (with a warning) any that are invalid.
+fn magic_matches(name: &str, matches: &[MatchElement]) -> Vec<MagicMatch> {
+    matches
+        .iter()
+        .filter_map(|m| match m.try_into() {
+            Ok(mm) => Some(mm),
+            Err(e) => {
+                warn!("{}: invalid magic match: {}", name, e);
+                None
+            }
+        })
+        .collect()
+}
+
+impl From<&TreeMagicElement> for TreeMagicRule {
+    fn from(tm: &TreeMagicElement) -> Self {
+        TreeMagicRule {
+            priority: tm.priority.unwrap_or(50),
+            matches: tm.matches.iter().map(TreeMatch::from).collect(),
+        }
+    }
+}
+
+impl From<&TreeMatchElement> for TreeMatch {
+    fn from(tm: &TreeMatchElement) -> Self {
+        TreeMatch {
+            path: tm.path.clone(),
+            file_type: tm.file_type,
+            match_case: tm.match_case,
+            executable: tm.executable,
+            non_empty: tm.non_empty,
+            mimetype: tm.mimetype.clone(),
+            children: tm.children.iter().map(TreeMatch::from).collect(),
         }
     }
 }
 
+impl TryFrom<&MatchElement> for MagicMatch {
+    type Error = String;
+
+    fn try_from(elt: &MatchElement) -> Result<Self, Self::Error> {
+        let (offset, range_length) = parse_magic_offset(&elt.offset)?;
+        let (value, word_size) = parse_magic_value(&elt.match_type, &elt.value)?;
+        let mask = if let Some(mask) = &elt.mask {
+            Some(parse_mask(&elt.match_type, mask, value.len())?)
+        } else {
+            None
+        };
+        let children = elt
+            .children
+            .iter()
+            .map(MagicMatch::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(MagicMatch {
+            offset,
+            range_length,
+            word_size,
+            value,
+            mask,
+            children,
+        })
+    }
+}
+
 impl From<GlobElement> for GlobRule {
     fn from(glob: GlobElement) -> Self {
         GlobRule {
@@ -164,3 +474,42 @@ impl From<&GlobElement> for GlobRule {
         }
     }
 }
+
+#[test]
+fn test_unknown_elements() {
+    let pkg = super::parse_mime_package_bytes(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<mime-info xmlns="http://www.freedesktop.org/standards/shared-mime-info">
+  <mime-type type="text/x-hackem">
+    <comment>Hackem file</comment>
+    <x-kde-hackem muche="1" foobie="bletch"/>
+    <glob pattern="*.hkm"/>
+    <future-element level="2"><detail/>text</future-element>
+  </mime-type>
+</mime-info>
+"#,
+    )
+    .expect("package should parse");
+    let records = pkg.into_records();
+    let rec = &records[0];
+    assert_eq!(rec.description.as_deref(), Some("Hackem file"));
+    assert_eq!(rec.globs.len(), 1);
+    assert_eq!(rec.extensions.len(), 2);
+    let kde: Vec<_> = rec.extensions_named("x-kde-hackem").collect();
+    assert_eq!(kde.len(), 1);
+    assert_eq!(kde[0].attribute("foobie"), Some("bletch"));
+    assert_eq!(
+        kde[0].attributes,
+        vec![
+            ("muche".to_string(), "1".to_string()),
+            ("foobie".to_string(), "bletch".to_string())
+        ]
+    );
+    assert_eq!(
+        rec.extensions_named("future-element")
+            .next()
+            .unwrap()
+            .attribute("level"),
+        Some("2")
+    );
+}
@@ -3,15 +3,51 @@ use std::fs;
 use std::io;
 use std::path::Path;
 
-use quick_xml::de::from_reader;
+use quick_xml::de::{from_reader, Deserializer};
+use quick_xml::DeError;
+use serde::de::DeserializeOwned;
 
-use super::xdg_package::MimeInfoPackage;
+use super::xdg_package::{MimeInfoPackage, MimeType};
 use super::XDGError;
 
 /// Parse a single package XML file from the shared mime database.
 pub fn parse_mime_package(path: &Path) -> Result<MimeInfoPackage, XDGError> {
-    let file = fs::File::open(path)?;
-    let read = io::BufReader::new(file);
-    let info: MimeInfoPackage = from_reader(read)?;
+    parse_package_located(path).map_err(|(e, _pos)| e)
+}
+
+/// Parse a package file, reporting the byte offset of any parse error.
+pub(crate) fn parse_package_located(
+    path: &Path,
+) -> Result<MimeInfoPackage, (XDGError, Option<usize>)> {
+    parse_located(path)
+}
+
+/// Parse an XML file, reporting the byte offset of any parse error.
+///
+/// Syntax errors are located where the reader found them; other errors (such
+/// as a missing attribute) at the end of the last event read, which is at or
+/// just after the offending element.  Errors opening the file have no offset.
+fn parse_located<T: DeserializeOwned>(path: &Path) -> Result<T, (XDGError, Option<usize>)> {
+    let file = fs::File::open(path).map_err(|e| (e.into(), None))?;
+    let mut de = Deserializer::from_reader(io::BufReader::new(file));
+    T::deserialize(&mut de).map_err(|e| {
+        let reader = de.get_ref().get_ref();
+        let pos = match e {
+            DeError::InvalidXml(_) => reader.error_position(),
+            _ => reader.buffer_position(),
+        };
+        (e.into(), Some(pos as usize))
+    })
+}
+
+/// Parse a single package XML document from memory.
+pub fn parse_mime_package_bytes(data: &[u8]) -> Result<MimeInfoPackage, XDGError> {
+    let info: MimeInfoPackage = from_reader(data)?;
     Ok(info)
 }
+
+/// Parse a per-type XML file (`media/subtype.xml`) written by
+/// `update-mime-info`, reporting the byte offset of any parse error.
+pub(crate) fn parse_type_file(path: &Path) -> Result<MimeType, (XDGError, Option<usize>)> {
+    parse_located(path)
+}
@@ -1,59 +1,87 @@
-//! Pin-based string cache.
+//! Shared, reference-counted string cache.
+//!
+//! Strings are only added to the cache while building (through `&mut`), so a
+//! populated cache can be shared read-only across threads.  Cached strings can
+//! also refer to static data, for precompiled databases.
 
+use std::cmp::Ordering;
 use std::fmt::{Debug, Display};
-use std::{borrow::Borrow, cell::RefCell, collections::HashSet, hash::Hash, ops::Deref, rc::Rc};
+use std::{borrow::Borrow, collections::HashSet, hash::Hash, ops::Deref, sync::Arc};
 
 /// Cache to reduce duplicated strings in memory.
 #[derive(Default)]
 pub struct StringCache {
-    cache: RefCell<HashSet<CachedString>>,
+    cache: HashSet<CachedString>,
 }
 
 impl StringCache {
-    pub fn cache<S: AsRef<str>>(&self, string: S) -> CachedString {
-        // slightly inefficient to search up to 3 times, but keeps borrow checker happy
-        let cache = self.cache.borrow();
-        if let Some(cached) = cache.get(string.as_ref()) {
+    /// Get the cached copy of a string, adding it to the cache if needed.
+    pub fn cache<S: AsRef<str>>(&mut self, string: S) -> CachedString {
+        if let Some(cached) = self.cache.get(string.as_ref()) {
             return cached.clone();
         }
-        drop(cache);
 
-        let cached = CachedString::create(string.as_ref().to_string());
-        let mut mcache = self.cache.borrow_mut();
-        mcache.insert(cached.clone());
+        let cached = CachedString::create(string.as_ref());
+        self.cache.insert(cached.clone());
         cached
     }
+
+    /// Get the cached copy of a string, or an uncached copy if it is not in
+    /// the cache.
+    pub fn lookup<S: AsRef<str>>(&self, string: S) -> CachedString {
+        if let Some(cached) = self.cache.get(string.as_ref()) {
+            cached.clone()
+        } else {
+            CachedString::create(string.as_ref())
+        }
+    }
 }
 
 /// A cached string.
 ///
-/// This string contains a reference to the underlying shared cached string, and
-/// can be cloned cheaply (it's just an [Rc]).
+/// This string contains a reference to the underlying shared cached string (or
+/// to static data), and can be cloned cheaply (it's just an [Arc]).
 #[derive(Clone)]
 pub struct CachedString {
-    string: Rc<String>,
+    string: Repr,
+}
+
+#[derive(Clone)]
+enum Repr {
+    Static(&'static str),
+    Shared(Arc<str>),
 }
 
 impl CachedString {
-    fn create(string: String) -> CachedString {
+    fn create(string: &str) -> CachedString {
         CachedString {
-            string: Rc::new(string),
+            string: Repr::Shared(Arc::from(string)),
         }
     }
 
-    fn as_str(&self) -> &str {
-        &self.string
+    /// Wrap a static string without copying it.
+    pub const fn from_static(string: &'static str) -> CachedString {
+        CachedString {
+            string: Repr::Static(string),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.string {
+            Repr::Static(s) => s,
+            Repr::Shared(s) => s,
+        }
     }
 }
 
 impl Debug for CachedString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Debug::fmt(self.string.as_ref(), f)
+        Debug::fmt(self.as_str(), f)
     }
 }
 impl Display for CachedString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(self.string.as_ref(), f)
+        Display::fmt(self.as_str(), f)
     }
 }
 
@@ -97,6 +125,18 @@ impl PartialEq<&str> for CachedString {
 
 impl Eq for CachedString {}
 
+impl PartialOrd for CachedString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CachedString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
 impl Hash for CachedString {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.as_str().hash(state)
@@ -0,0 +1,94 @@
+//! Lookup tables that are either built at runtime or borrowed from static data.
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A map that is a hash map when built at runtime, or a sorted static slice
+/// when loaded from a precompiled database.
+///
+/// The static form is searched by binary search, and converted to a hash map
+/// the first time the table is modified.
+#[derive(Debug, Clone)]
+pub enum Table<K: 'static, V: 'static> {
+    /// Entries sorted by key.
+    Static(&'static [(K, V)]),
+    Owned(HashMap<K, V>),
+}
+
+impl<K, V> Default for Table<K, V> {
+    fn default() -> Self {
+        Table::Owned(HashMap::new())
+    }
+}
+
+impl<K, V> Table<K, V>
+where
+    K: Hash + Eq + Ord + Clone,
+    V: Clone,
+{
+    /// Look up a key.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + Ord + ?Sized,
+    {
+        match self {
+            Table::Static(entries) => entries
+                .binary_search_by(|(k, _)| k.borrow().cmp(key))
+                .ok()
+                .map(|i| &entries[i].1),
+            Table::Owned(map) => map.get(key),
+        }
+    }
+
+    /// Get the number of entries.
+    pub fn len(&self) -> usize {
+        match self {
+            Table::Static(entries) => entries.len(),
+            Table::Owned(map) => map.len(),
+        }
+    }
+
+    /// Check whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over the entries, in no particular order.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (&K, &V)> + '_> {
+        match self {
+            Table::Static(entries) => Box::new(entries.iter().map(|(k, v)| (k, v))),
+            Table::Owned(map) => Box::new(map.iter()),
+        }
+    }
+
+    /// Get a mutable map, copying static entries if needed.
+    pub fn to_mut(&mut self) -> &mut HashMap<K, V> {
+        if let Table::Static(entries) = self {
+            *self = Table::Owned(entries.iter().cloned().collect());
+        }
+        match self {
+            Table::Owned(map) => map,
+            Table::Static(_) => unreachable!("static table was just converted"),
+        }
+    }
+
+    /// Get the entries sorted by key.
+    pub fn sorted(&self) -> Vec<(&K, &V)> {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+}
+
+#[test]
+fn test_static_table() {
+    static ENTRIES: &[(&str, i32)] = &[("hackem", 1), ("muche", 2)];
+    let mut table = Table::Static(ENTRIES);
+    assert_eq!(table.get("muche"), Some(&2));
+    assert_eq!(table.get("foobie"), None);
+    table.to_mut().insert("foobie", 3);
+    assert_eq!(table.len(), 3);
+    assert_eq!(table.get("hackem"), Some(&1));
+    assert_eq!(table.get("foobie"), Some(&3));
+}
@@ -0,0 +1,135 @@
+//! Text vs. binary detection for files no other rule identifies.
+//!
+//! The shared MIME info spec says that a file whose first bytes contain no
+//! control characters (other than whitespace) should be treated as
+//! `text/plain`.  [TextRules] configures exactly how that check is done.
+
+/// Rules for detecting plain-text files from their content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextRules {
+    /// Whether to detect text files at all.
+    pub enabled: bool,
+    /// Number of leading bytes to examine.
+    pub window: usize,
+    /// Whether a Unicode byte-order mark marks the file as text regardless of
+    /// the remaining content.
+    pub accept_bom: bool,
+    /// Whether the examined bytes must be valid UTF-8.
+    pub require_utf8: bool,
+    /// Control characters that are allowed in text (whitespace by default).
+    pub allowed_controls: Vec<u8>,
+}
+
+const BOMS: &[&[u8]] = &[b"\xef\xbb\xbf", b"\xff\xfe", b"\xfe\xff"];
+
+impl Default for TextRules {
+    fn default() -> Self {
+        TextRules {
+            enabled: true,
+            window: 512,
+            accept_bom: true,
+            require_utf8: true,
+            allowed_controls: b"\t\n\x0b\x0c\r".to_vec(),
+        }
+    }
+}
+
+impl TextRules {
+    /// Rules that never detect text.
+    pub fn disabled() -> TextRules {
+        TextRules {
+            enabled: false,
+            ..TextRules::default()
+        }
+    }
+
+    /// Set the number of leading bytes to examine.
+    pub fn window(self, window: usize) -> TextRules {
+        TextRules { window, ..self }
+    }
+
+    /// Check whether data (the beginning of a file) looks like text.
+    pub fn is_text(&self, data: &[u8]) -> bool {
+        if !self.enabled || data.is_empty() {
+            return false;
+        }
+        if self.accept_bom && BOMS.iter().any(|bom| data.starts_with(bom)) {
+            return true;
+        }
+
+        let data = &data[..data.len().min(self.window)];
+        let controls_ok = data
+            .iter()
+            .all(|c| !is_control(*c) || self.allowed_controls.contains(c));
+        if !controls_ok {
+            return false;
+        }
+
+        if self.require_utf8 {
+            match std::str::from_utf8(data) {
+                Ok(_) => true,
+                // the window may cut off a multi-byte character
+                Err(e) => e.error_len().is_none(),
+            }
+        } else {
+            true
+        }
+    }
+}
+
+fn is_control(c: u8) -> bool {
+    c < 0x20 || c == 0x7f
+}
+
+#[test]
+fn test_plain_ascii() {
+    let rules = TextRules::default();
+    assert!(rules.is_text(b"Hackem muche.\n\tFoobie bletch.\r\n"));
+}
+
+#[test]
+fn test_utf8() {
+    let rules = TextRules::default();
+    assert!(rules.is_text("Ελληνικά και русский\n".as_bytes()));
+}
+
+#[test]
+fn test_truncated_utf8() {
+    let rules = TextRules::default().window(4);
+    // the window cuts 'λ' in half
+    assert!(rules.is_text("abcλ".as_bytes()));
+}
+
+#[test]
+fn test_binary() {
+    let rules = TextRules::default();
+    assert!(!rules.is_text(b"\x7fELF\x02\x01\x01\0"));
+    assert!(!rules.is_text(b"text with a \0 in it"));
+}
+
+#[test]
+fn test_invalid_utf8() {
+    let rules = TextRules::default();
+    assert!(!rules.is_text(b"caf\xe9 au lait"));
+    let latin = TextRules {
+        require_utf8: false,
+        ..TextRules::default()
+    };
+    assert!(latin.is_text(b"caf\xe9 au lait"));
+}
+
+#[test]
+fn test_bom() {
+    let rules = TextRules::default();
+    assert!(rules.is_text(b"\xff\xfeh\0i\0"));
+    let no_bom = TextRules {
+        accept_bom: false,
+        ..TextRules::default()
+    };
+    assert!(!no_bom.is_text(b"\xff\xfeh\0i\0"));
+}
+
+#[test]
+fn test_disabled() {
+    assert!(!TextRules::disabled().is_text(b"hello"));
+}
@@ -0,0 +1,170 @@
+//! Minimal XML scanning to find a document's root element.
+//!
+//! This only understands enough XML to skip the prolog (declaration,
+//! processing instructions, comments and doctype) and read the root element's
+//! start tag, so it works on the truncated file headers used for sniffing.
+
+/// The namespace URI and local name of an XML document's root element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlRoot {
+    pub namespace_uri: String,
+    pub local_name: String,
+}
+
+/// Find the root element of an XML document from its first bytes.
+///
+/// Returns [None] if the data does not look like XML or the root start tag
+/// is incomplete.
+pub fn find_root(data: &[u8]) -> Option<XmlRoot> {
+    let mut data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    loop {
+        data = skip_whitespace(data);
+        if data.starts_with(b"<?") {
+            data = skip_past(data, b"?>")?;
+        } else if data.starts_with(b"<!--") {
+            data = skip_past(data, b"-->")?;
+        } else if data.starts_with(b"<!") {
+            data = skip_declaration(data)?;
+        } else if data.starts_with(b"<") {
+            return parse_start_tag(&data[1..]);
+        } else {
+            return None;
+        }
+    }
+}
+
+fn skip_whitespace(data: &[u8]) -> &[u8] {
+    let n = data.iter().take_while(|c| c.is_ascii_whitespace()).count();
+    &data[n..]
+}
+
+fn skip_past<'a>(data: &'a [u8], end: &[u8]) -> Option<&'a [u8]> {
+    let pos = data.windows(end.len()).position(|w| w == end)?;
+    Some(&data[pos + end.len()..])
+}
+
+/// Skip a `<!DOCTYPE ...>` declaration, including any internal subset.
+fn skip_declaration(data: &[u8]) -> Option<&[u8]> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in data.iter().enumerate().skip(2) {
+        match (quote, c) {
+            (Some(q), c) if *c == q => quote = None,
+            (Some(_), _) => (),
+            (None, b'"' | b'\'') => quote = Some(*c),
+            (None, b'[') => depth += 1,
+            (None, b']') => depth -= 1,
+            (None, b'>') if depth == 0 => return Some(&data[i + 1..]),
+            _ => (),
+        }
+    }
+    None
+}
+
+fn is_name_end(c: u8) -> bool {
+    c.is_ascii_whitespace() || c == b'/' || c == b'>' || c == b'='
+}
+
+fn parse_start_tag(data: &[u8]) -> Option<XmlRoot> {
+    let n = data.iter().take_while(|c| !is_name_end(**c)).count();
+    if n == 0 || n == data.len() {
+        return None;
+    }
+    let name = std::str::from_utf8(&data[..n]).ok()?;
+    let (prefix, local) = match name.split_once(':') {
+        Some((p, l)) => (Some(p), l),
+        None => (None, name),
+    };
+
+    let mut rest = &data[n..];
+    let mut namespace = None;
+    loop {
+        rest = skip_whitespace(rest);
+        match rest.first()? {
+            b'>' | b'/' => break,
+            _ => (),
+        }
+        let an = rest.iter().take_while(|c| !is_name_end(**c)).count();
+        let attr = std::str::from_utf8(&rest[..an]).ok()?;
+        rest = skip_whitespace(&rest[an..]);
+        rest = rest.strip_prefix(b"=")?;
+        rest = skip_whitespace(rest);
+        let quote = *rest.first()?;
+        if quote != b'"' && quote != b'\'' {
+            return None;
+        }
+        let vn = rest[1..].iter().position(|c| *c == quote)?;
+        let value = std::str::from_utf8(&rest[1..vn + 1]).ok()?;
+        rest = &rest[vn + 2..];
+
+        let wanted = match (prefix, attr.strip_prefix("xmlns")) {
+            (None, Some("")) => true,
+            (Some(p), Some(ap)) => ap.strip_prefix(':') == Some(p),
+            _ => false,
+        };
+        if wanted {
+            namespace = Some(value.to_string());
+        }
+    }
+
+    Some(XmlRoot {
+        namespace_uri: namespace.unwrap_or_default(),
+        local_name: local.to_string(),
+    })
+}
+
+#[cfg(test)]
+fn root(ns: &str, local: &str) -> Option<XmlRoot> {
+    Some(XmlRoot {
+        namespace_uri: ns.into(),
+        local_name: local.into(),
+    })
+}
+
+#[test]
+fn test_simple_root() {
+    assert_eq!(find_root(b"<doc/>"), root("", "doc"));
+}
+
+#[test]
+fn test_default_namespace() {
+    let xml = br#"<?xml version="1.0"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="10">"#;
+    assert_eq!(find_root(xml), root("http://www.w3.org/2000/svg", "svg"));
+}
+
+#[test]
+fn test_prefixed_namespace() {
+    let xml = br#"<rdf:RDF xmlns="http://purl.org/rss/1.0/"
+    xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>"#;
+    assert_eq!(
+        find_root(xml),
+        root("http://www.w3.org/1999/02/22-rdf-syntax-ns#", "RDF")
+    );
+}
+
+#[test]
+fn test_skip_prolog() {
+    let xml = b"\xef\xbb\xbf<?xml version=\"1.0\"?>
+<!-- a <comment> -->
+<?xml-stylesheet href=\"style.css\"?>
+<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\" [
+  <!ENTITY hackem \"muche>\">
+]>
+<html xmlns=\"http://www.w3.org/1999/xhtml\">";
+    assert_eq!(find_root(xml), root("http://www.w3.org/1999/xhtml", "html"));
+}
+
+#[test]
+fn test_truncated() {
+    assert_eq!(
+        find_root(b"<?xml version=\"1.0\"?><svg xmlns=\"http://www.w3"),
+        None
+    );
+    assert_eq!(find_root(b"<!-- never closed"), None);
+}
+
+#[test]
+fn test_not_xml() {
+    assert_eq!(find_root(b"hackem muche"), None);
+}